@@ -0,0 +1,89 @@
+use failure::Fail;
+use futures::prelude::*;
+use futures_cpupool::CpuPool;
+use lettre::smtp::authentication::Credentials;
+use lettre::{ClientSecurity, ClientTlsParameters, SmtpClient, Transport};
+use lettre_email::EmailBuilder;
+use native_tls::TlsConnector;
+
+use super::error::*;
+use super::AlertSink;
+use models::*;
+
+/// Emails alerts through SMTP server. Sending is blocking, so it's done on a separate thread pool.
+#[derive(Clone)]
+pub struct EmailSink {
+    cpu_pool: CpuPool,
+    host: String,
+    port: u16,
+    tls: bool,
+    username: Option<String>,
+    password: Option<String>,
+    from: String,
+    to: Vec<String>,
+}
+
+impl EmailSink {
+    pub fn new(
+        host: String,
+        port: u16,
+        tls: bool,
+        username: Option<String>,
+        password: Option<String>,
+        from: String,
+        to: Vec<String>,
+    ) -> Self {
+        Self {
+            cpu_pool: CpuPool::new(1),
+            host,
+            port,
+            tls,
+            username,
+            password,
+            from,
+            to,
+        }
+    }
+
+    fn send_blocking(&self, subject: String, text: String) -> Result<(), Error> {
+        let mut builder = EmailBuilder::new().from(self.from.as_str()).subject(subject).text(text);
+        for to in &self.to {
+            builder = builder.to(to.as_str());
+        }
+        let email = builder
+            .build()
+            .map_err(ectx!(try ErrorSource::Smtp, ErrorKind::MalformedInput => self.from, self.to))?;
+        let security = if self.tls {
+            let connector = TlsConnector::new().map_err(ectx!(try ErrorSource::Tls, ErrorKind::Internal))?;
+            ClientSecurity::Required(ClientTlsParameters::new(self.host.clone(), connector))
+        } else {
+            ClientSecurity::None
+        };
+        let mut client = SmtpClient::new((self.host.as_str(), self.port), security)
+            .map_err(ectx!(try ErrorSource::Smtp, ErrorKind::Internal => self.host, self.port))?;
+        if let (Some(username), Some(password)) = (self.username.clone(), self.password.clone()) {
+            client = client.credentials(Credentials::new(username, password));
+        }
+        client
+            .transport()
+            .send(email.into())
+            .map(|_| ())
+            .map_err(ectx!(ErrorSource::Smtp, ErrorKind::Internal => self.host, self.port))
+    }
+}
+
+impl AlertSink for EmailSink {
+    fn send(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        let self_clone = self.clone();
+        let subject = format!("[{:?}] bitcoin proxy alert {}", alert.severity, alert.alias());
+        let text = alert.message.clone();
+        Box::new(self.cpu_pool.spawn_fn(move || self_clone.send_blocking(subject, text)))
+    }
+
+    fn resolve(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        let self_clone = self.clone();
+        let subject = format!("[Resolved] bitcoin proxy alert {}", alert.alias());
+        let text = format!("Condition cleared: {}", alert.message);
+        Box::new(self.cpu_pool.spawn_fn(move || self_clone.send_blocking(subject, text)))
+    }
+}
@@ -0,0 +1,34 @@
+use failure::{Backtrace, Context, Fail};
+use std::fmt;
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "alerts error - malformed input")]
+    MalformedInput,
+    #[fail(display = "alerts error - internal error")]
+    Internal,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorSource {
+    #[fail(display = "alerts source - error inside of OpsGenie client")]
+    OpsGenie,
+    #[fail(display = "alerts source - error inside of webhook client")]
+    Webhook,
+    #[fail(display = "alerts source - error inside of lettre library")]
+    Smtp,
+    #[fail(display = "alerts source - error inside of native tls library")]
+    Tls,
+    #[fail(display = "alerts source - error converting to json")]
+    Json,
+}
+
+derive_error_impls!();
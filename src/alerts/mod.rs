@@ -0,0 +1,166 @@
+//! Alert sinks and routing of healthcheck alerts to them.
+
+mod email;
+mod error;
+mod opsgenie;
+mod pagerduty;
+mod slack;
+mod webhook;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future;
+use futures::prelude::*;
+
+pub use self::email::*;
+pub use self::error::*;
+pub use self::pagerduty::*;
+pub use self::slack::*;
+pub use self::webhook::*;
+use client::{HttpClient, OpsGenieClientImpl, WebhookClientImpl};
+use config::{AlertRoute, AlertSinkKind, Config};
+use models::*;
+use utils::log_error;
+
+/// Name of the sink configured with `[opsgenie]` section
+pub const OPSGENIE_SINK: &str = "opsgenie";
+/// Repeat interval of active alerts when `alerts.repeat_interval` is not configured, in seconds
+const DEFAULT_REPEAT_INTERVAL: u64 = 3600;
+
+/// Destination for alerts
+pub trait AlertSink: Send + Sync + 'static {
+    fn send(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send>;
+    /// Notifies that condition of previously sent alert is cleared
+    fn resolve(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send>;
+}
+
+struct ActiveAlert {
+    alert: Alert,
+    sent_at: Instant,
+}
+
+/// Sends alerts to sinks chosen by routing rules. Repeats of an active alert are suppressed
+/// within repeat interval, and sinks are notified once its condition clears.
+#[derive(Clone)]
+pub struct Alerter {
+    sinks: Vec<(String, Arc<AlertSink>)>,
+    routes: Vec<AlertRoute>,
+    repeat_interval: Duration,
+    active: Arc<Mutex<HashMap<String, ActiveAlert>>>,
+}
+
+impl Alerter {
+    pub fn new<C: HttpClient + Clone>(config: &Config, client: C) -> Self {
+        let mut sinks: Vec<(String, Arc<AlertSink>)> = Vec::new();
+        if config.opsgenie.enabled {
+            sinks.push((OPSGENIE_SINK.to_string(), Arc::new(OpsGenieClientImpl::new(config, client.clone()))));
+        }
+        let webhook_client = WebhookClientImpl::new(Arc::new(client));
+        let alerts = config.alerts.clone();
+        let routes = alerts.as_ref().map(|alerts| alerts.routes.clone()).unwrap_or_default();
+        let repeat_interval = alerts
+            .as_ref()
+            .and_then(|alerts| alerts.repeat_interval)
+            .unwrap_or(DEFAULT_REPEAT_INTERVAL);
+        for sink in alerts.map(|alerts| alerts.sinks).unwrap_or_default() {
+            let name = sink.name.clone();
+            let sink: Arc<AlertSink> = match sink.kind {
+                AlertSinkKind::Slack { url } => Arc::new(SlackSink::new(webhook_client.clone(), url)),
+                AlertSinkKind::PagerDuty { routing_key, url } => Arc::new(PagerDutySink::new(webhook_client.clone(), routing_key, url)),
+                AlertSinkKind::Webhook { url, secret } => Arc::new(WebhookSink::new(webhook_client.clone(), url, secret)),
+                AlertSinkKind::Email {
+                    host,
+                    port,
+                    tls,
+                    username,
+                    password,
+                    from,
+                    to,
+                } => Arc::new(EmailSink::new(host, port, tls, username, password, from, to)),
+            };
+            sinks.push((name, sink));
+        }
+        for route in &routes {
+            for name in &route.sinks {
+                if !sinks.iter().any(|(sink_name, _)| sink_name == name) {
+                    warn!("Alert route refers to unknown or disabled sink {}", name);
+                }
+            }
+        }
+        Self {
+            sinks,
+            routes,
+            repeat_interval: Duration::from_secs(repeat_interval),
+            active: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Sends alert to every matching sink, unless the same alert was sent recently with the same or higher severity
+    pub fn notify(&self, alert: Alert) -> Box<Future<Item = (), Error = ()> + Send> {
+        let alias = alert.alias();
+        {
+            let mut active = self.active.lock().unwrap();
+            let suppressed = active
+                .get(&alias)
+                .map(|active| active.sent_at.elapsed() < self.repeat_interval && active.alert.severity >= alert.severity)
+                .unwrap_or(false);
+            if suppressed {
+                debug!("Suppressed repeated alert {}", alias);
+                return Box::new(future::ok(()));
+            }
+            active.insert(
+                alias,
+                ActiveAlert {
+                    alert: alert.clone(),
+                    sent_at: Instant::now(),
+                },
+            );
+        }
+        Box::new(self.dispatch(&alert, |sink, alert| sink.send(alert)))
+    }
+
+    /// Resolves active alert of `kind` about `node`, does nothing if there is none
+    pub fn resolve(&self, kind: AlertKind, node: Option<&str>) -> Box<Future<Item = (), Error = ()> + Send> {
+        let alias = alias(kind, node);
+        let resolved = self.active.lock().unwrap().remove(&alias);
+        match resolved {
+            Some(active) => {
+                info!("Resolved alert {}", alias);
+                Box::new(self.dispatch(&active.alert, |sink, alert| sink.resolve(alert)))
+            }
+            None => Box::new(future::ok(())),
+        }
+    }
+
+    /// Calls `f` for every sink the alert is routed to. Sink errors are logged, so that one broken sink doesn't affect others.
+    fn dispatch<F>(&self, alert: &Alert, f: F) -> impl Future<Item = (), Error = ()> + Send
+    where
+        F: Fn(&AlertSink, &Alert) -> Box<Future<Item = (), Error = Error> + Send>,
+    {
+        let calls: Vec<_> = self
+            .sinks
+            .iter()
+            .filter(|(name, _)| self.routes.is_empty() || self.routes.iter().any(|route| matches(route, name, alert)))
+            .map(|(_, sink)| {
+                f(sink.as_ref(), alert).then(|r| {
+                    if let Err(e) = r {
+                        log_error(&e);
+                    }
+                    Ok::<_, ()>(())
+                })
+            })
+            .collect();
+        if calls.is_empty() {
+            warn!("Alert is not routed to any sink: {}", alert.message);
+        }
+        future::join_all(calls).map(|_| ())
+    }
+}
+
+fn matches(route: &AlertRoute, sink: &str, alert: &Alert) -> bool {
+    route.sinks.iter().any(|name| name == sink)
+        && (route.kinds.is_empty() || route.kinds.contains(&alert.kind))
+        && alert.severity >= route.min_severity
+}
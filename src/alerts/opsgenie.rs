@@ -0,0 +1,21 @@
+use failure::Fail;
+use futures::prelude::*;
+
+use super::error::*;
+use super::AlertSink;
+use client::{OpsGenieClient, OpsGenieClientImpl};
+use models::*;
+
+impl AlertSink for OpsGenieClientImpl {
+    fn send(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        Box::new(self.notify(alert).map_err(ectx!(ErrorSource::OpsGenie, ErrorKind::Internal)))
+    }
+
+    fn resolve(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        let alias = alert.alias();
+        Box::new(
+            self.close(alias.clone())
+                .map_err(ectx!(ErrorSource::OpsGenie, ErrorKind::Internal => alias)),
+        )
+    }
+}
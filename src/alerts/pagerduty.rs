@@ -0,0 +1,72 @@
+use failure::Fail;
+use futures::prelude::*;
+
+use super::error::*;
+use super::AlertSink;
+use client::{WebhookClient, WebhookClientImpl};
+use models::*;
+use redact;
+
+const EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Triggers incidents with PagerDuty Events API v2
+#[derive(Clone)]
+pub struct PagerDutySink {
+    client: WebhookClientImpl,
+    routing_key: String,
+    url: String,
+}
+
+impl PagerDutySink {
+    pub fn new(client: WebhookClientImpl, routing_key: String, url: Option<String>) -> Self {
+        Self {
+            client,
+            routing_key,
+            url: url.unwrap_or_else(|| EVENTS_URL.to_string()),
+        }
+    }
+}
+
+impl PagerDutySink {
+    fn enqueue(&self, body: String) -> Box<Future<Item = (), Error = Error> + Send> {
+        let url = redact::url(&self.url);
+        Box::new(
+            self.client
+                .send(self.url.clone(), None, body)
+                .map_err(ectx!(ErrorSource::Webhook, ErrorKind::Internal => url)),
+        )
+    }
+}
+
+impl AlertSink for PagerDutySink {
+    fn send(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        let severity = match alert.severity {
+            AlertSeverity::Info => "info",
+            AlertSeverity::Warning => "warning",
+            AlertSeverity::Critical => "critical",
+        };
+        let body = json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": alert.alias(),
+            "payload": {
+                "summary": alert.message,
+                "source": alert.node.clone().unwrap_or_else(|| "bitcoin-proxy".to_string()),
+                "severity": severity,
+                "class": alert.kind,
+            }
+        })
+        .to_string();
+        self.enqueue(body)
+    }
+
+    fn resolve(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        let body = json!({
+            "routing_key": self.routing_key,
+            "event_action": "resolve",
+            "dedup_key": alert.alias(),
+        })
+        .to_string();
+        self.enqueue(body)
+    }
+}
@@ -0,0 +1,43 @@
+use failure::Fail;
+use futures::prelude::*;
+
+use super::error::*;
+use super::AlertSink;
+use client::{WebhookClient, WebhookClientImpl};
+use models::*;
+use redact;
+
+/// Posts alerts to Slack or Mattermost incoming webhook
+#[derive(Clone)]
+pub struct SlackSink {
+    client: WebhookClientImpl,
+    url: String,
+}
+
+impl SlackSink {
+    pub fn new(client: WebhookClientImpl, url: String) -> Self {
+        Self { client, url }
+    }
+}
+
+impl SlackSink {
+    fn post(&self, text: String) -> Box<Future<Item = (), Error = Error> + Send> {
+        let body = json!({ "text": text }).to_string();
+        let url = redact::url(&self.url);
+        Box::new(
+            self.client
+                .send(self.url.clone(), None, body)
+                .map_err(ectx!(ErrorSource::Webhook, ErrorKind::Internal => url)),
+        )
+    }
+}
+
+impl AlertSink for SlackSink {
+    fn send(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        self.post(format!("*[{:?}]* {}", alert.severity, alert.message))
+    }
+
+    fn resolve(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        self.post(format!("*[Resolved]* {}", alert.message))
+    }
+}
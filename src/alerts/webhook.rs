@@ -0,0 +1,60 @@
+use failure::Fail;
+use futures::prelude::*;
+use serde_json;
+
+use super::error::*;
+use super::AlertSink;
+use client::{WebhookClient, WebhookClientImpl};
+use models::*;
+use redact;
+
+/// Posts alerts as json payload to arbitrary url
+#[derive(Clone)]
+pub struct WebhookSink {
+    client: WebhookClientImpl,
+    url: String,
+    secret: Option<String>,
+}
+
+impl WebhookSink {
+    pub fn new(client: WebhookClientImpl, url: String, secret: Option<String>) -> Self {
+        Self { client, url, secret }
+    }
+}
+
+impl WebhookSink {
+    fn post(&self, event: AlertEvent) -> Box<Future<Item = (), Error = Error> + Send> {
+        let client = self.client.clone();
+        let url = self.url.clone();
+        let url_clone = redact::url(&url);
+        let secret = self.secret.clone();
+        Box::new(
+            serde_json::to_string(&WebhookPayload::new(event))
+                .map_err(ectx!(ErrorSource::Json, ErrorKind::Internal))
+                .into_future()
+                .and_then(move |body| {
+                    client
+                        .send(url, secret, body)
+                        .map_err(ectx!(ErrorSource::Webhook, ErrorKind::Internal => url_clone))
+                }),
+        )
+    }
+}
+
+impl AlertSink for WebhookSink {
+    fn send(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        self.post(AlertEvent {
+            status: AlertStatus::Firing,
+            alias: alert.alias(),
+            alert: alert.clone(),
+        })
+    }
+
+    fn resolve(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
+        self.post(AlertEvent {
+            status: AlertStatus::Resolved,
+            alias: alert.alias(),
+            alert: alert.clone(),
+        })
+    }
+}
@@ -0,0 +1,86 @@
+//! One line json log of served requests, written to `access_log` target.
+
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use hyper::{HeaderMap, Method, Uri};
+use serde_json;
+use uuid::Uuid;
+
+use client::REQUEST_ID_HEADER;
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Node that served a proxied request, set by controllers as a response extension
+#[derive(Debug, Clone)]
+pub struct UpstreamNode(pub String);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessLogEntry {
+    pub request_id: String,
+    pub client: Option<String>,
+    pub caller: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub rpc_method: Option<String>,
+    pub node: Option<String>,
+    pub status: u16,
+    pub duration_ms: u64,
+    pub request_bytes: usize,
+    pub response_bytes: Option<u64>,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl AccessLogEntry {
+    pub fn new(request_id: String, client: Option<SocketAddr>, caller: Option<String>, method: &Method, uri: &Uri) -> Self {
+        Self {
+            request_id,
+            client: client.map(|addr| addr.to_string()),
+            caller,
+            method: method.to_string(),
+            path: uri.path().to_string(),
+            rpc_method: None,
+            node: None,
+            status: 0,
+            duration_ms: 0,
+            request_bytes: 0,
+            response_bytes: None,
+            started: Some(Instant::now()),
+        }
+    }
+
+    pub fn log(mut self) {
+        if let Some(started) = self.started {
+            let elapsed = started.elapsed();
+            self.duration_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+        }
+        match serde_json::to_string(&self) {
+            Ok(line) => info!(target: "access_log", "{}", line),
+            Err(e) => warn!("Couldn't serialize access log entry: {}", e),
+        }
+    }
+}
+
+/// Request id from `X-Request-Id` header if it's sane, otherwise a new one
+pub fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.chars().all(|c| c.is_ascii_graphic()))
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Json rpc method of request body, `batch` for batch requests
+pub fn rpc_method(body: &[u8]) -> Option<String> {
+    match serde_json::from_slice::<serde_json::Value>(body).ok()? {
+        serde_json::Value::Object(object) => object
+            .get("method")
+            .and_then(|method| method.as_str())
+            .map(|method| method.to_string()),
+        serde_json::Value::Array(_) => Some("batch".to_string()),
+        _ => None,
+    }
+}
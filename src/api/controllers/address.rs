@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use failure::Fail;
+use futures::future;
+use futures::prelude::*;
+use hyper::header::HeaderValue;
+use regex::Regex;
+
+use super::super::utils::response_with_model;
+use super::Context;
+use super::ControllerFuture;
+use super::{ErrorContext, ErrorKind};
+use indexer::IndexStorage;
+
+lazy_static! {
+    static ref ADDRESS_REGEX: Regex = Regex::new(r"^[a-zA-Z0-9]{14,90}$").unwrap();
+}
+
+pub fn get_address_utxo(ctx: &Context, address: String) -> ControllerFuture {
+    Box::new(
+        index_for(ctx, &address)
+            .into_future()
+            .and_then(move |index| {
+                index
+                    .utxo(&address)
+                    .map_err(ectx!(ErrorKind::Internal => address))
+                    .map(|utxo| (index, utxo))
+            })
+            .and_then(|(index, utxo)| with_indexed_from(&index, response_with_model(&utxo))),
+    )
+}
+
+pub fn get_address_balance(ctx: &Context, address: String) -> ControllerFuture {
+    Box::new(
+        index_for(ctx, &address)
+            .into_future()
+            .and_then(move |index| index.balance(&address).map_err(ectx!(ErrorKind::Internal => address)))
+            .and_then(|balance| response_with_model(&balance)),
+    )
+}
+
+pub fn get_address_txs(ctx: &Context, address: String) -> ControllerFuture {
+    Box::new(
+        index_for(ctx, &address)
+            .into_future()
+            .and_then(move |index| {
+                index
+                    .transactions(&address)
+                    .map_err(ectx!(ErrorKind::Internal => address))
+                    .map(|txs| (index, txs))
+            })
+            .and_then(|(index, txs)| with_indexed_from(&index, response_with_model(&txs))),
+    )
+}
+
+/// Tells that outputs and spends in blocks below `X-Indexed-From` height are missing from the response
+fn with_indexed_from(index: &IndexStorage, resp: ControllerFuture) -> ControllerFuture {
+    let indexed_from = match index.indexed_from() {
+        Ok(indexed_from) => indexed_from,
+        Err(e) => return Box::new(future::err(ectx!(err e, ErrorKind::Internal))),
+    };
+    Box::new(resp.map(move |mut resp| {
+        if let Some(indexed_from) = indexed_from {
+            resp.headers_mut().insert("X-Indexed-From", HeaderValue::from(indexed_from));
+        }
+        resp
+    }))
+}
+
+fn index_for(ctx: &Context, address: &str) -> Result<Arc<IndexStorage>, super::Error> {
+    if !ADDRESS_REGEX.is_match(address) {
+        return Err(ectx!(err ErrorContext::Address, ErrorKind::BadRequest => address));
+    }
+    ctx.index
+        .clone()
+        .ok_or_else(|| ectx!(err ErrorContext::IndexDisabled, ErrorKind::NotFound))
+}
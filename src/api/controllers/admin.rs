@@ -0,0 +1,74 @@
+use failure::Fail;
+use futures::future;
+use futures::prelude::*;
+use hyper::header::AUTHORIZATION;
+
+use super::super::utils::{parse_body, response_with_model};
+use super::Context;
+use super::ControllerFuture;
+use super::{Error, ErrorContext, ErrorKind};
+use logger::{self, LogLevels};
+use models::*;
+
+/// Nodes with their quarantine state and history
+pub fn get_admin_nodes(ctx: &Context) -> ControllerFuture {
+    if let Err(e) = authorize(ctx) {
+        return Box::new(future::err(e));
+    }
+    let nodes: Vec<BitcoinNode> = ctx.nodes.lock().unwrap().values().cloned().collect();
+    response_with_model(&nodes)
+}
+
+/// Checks hash chain of the audit log
+pub fn get_admin_audit(ctx: &Context) -> ControllerFuture {
+    if let Err(e) = authorize(ctx) {
+        return Box::new(future::err(e));
+    }
+    let audit = match ctx.audit {
+        Some(ref audit) => audit,
+        None => return Box::new(future::err(ectx!(err ErrorContext::Audit, ErrorKind::NotFound))),
+    };
+    match audit.verify() {
+        Ok(verification) => response_with_model(&verification),
+        Err(e) => Box::new(future::err(ectx!(err e, ErrorContext::Audit, ErrorKind::Internal))),
+    }
+}
+
+/// Current levels of log targets
+pub fn get_admin_log(ctx: &Context) -> ControllerFuture {
+    if let Err(e) = authorize(ctx) {
+        return Box::new(future::err(e));
+    }
+    response_with_model(&logger::levels())
+}
+
+/// Replaces levels of log targets until restart
+pub fn put_admin_log(ctx: &Context) -> ControllerFuture {
+    if let Err(e) = authorize(ctx) {
+        return Box::new(future::err(e));
+    }
+    Box::new(parse_body::<LogLevels>(ctx.body.clone()).and_then(|levels| {
+        logger::set_levels(levels.clone());
+        response_with_model(&levels)
+    }))
+}
+
+/// Admin API is hidden when not configured and requires bearer token otherwise
+pub(super) fn authorize(ctx: &Context) -> Result<(), Error> {
+    let token = match ctx.config.admin {
+        Some(ref admin) => admin.token.clone(),
+        None => return Err(ectx!(err ErrorContext::Admin, ErrorKind::NotFound)),
+    };
+    let expected = format!("Bearer {}", token);
+    let authorized = ctx
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value == expected)
+        .unwrap_or(false);
+    if authorized {
+        Ok(())
+    } else {
+        Err(ectx!(err ErrorContext::Token, ErrorKind::Unauthorized))
+    }
+}
@@ -0,0 +1,33 @@
+use failure::Fail;
+use futures::future;
+
+use super::super::utils::response_with_model;
+use super::Context;
+use super::ControllerFuture;
+use super::{Error, ErrorContext, ErrorKind};
+use tracker::TxTracker;
+
+pub fn get_broadcasts(ctx: &Context) -> ControllerFuture {
+    match tracker_for(ctx) {
+        Ok(tracker) => response_with_model(&tracker.list()),
+        Err(e) => Box::new(future::err(e)),
+    }
+}
+
+pub fn get_broadcast(ctx: &Context, txid: String) -> ControllerFuture {
+    let tx = tracker_for(ctx).and_then(|tracker| {
+        tracker
+            .get(&txid)
+            .ok_or_else(|| ectx!(err ErrorContext::Transaction, ErrorKind::NotFound => txid))
+    });
+    match tx {
+        Ok(tx) => response_with_model(&tx),
+        Err(e) => Box::new(future::err(e)),
+    }
+}
+
+fn tracker_for(ctx: &Context) -> Result<TxTracker, Error> {
+    ctx.tracker
+        .clone()
+        .ok_or_else(|| ectx!(err ErrorContext::TrackingDisabled, ErrorKind::NotFound))
+}
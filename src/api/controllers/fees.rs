@@ -0,0 +1,16 @@
+use failure::Fail;
+use futures::prelude::*;
+
+use super::super::utils::response_with_model;
+use super::Context;
+use super::ControllerFuture;
+use super::ErrorKind;
+
+pub fn get_fees(ctx: &Context) -> ControllerFuture {
+    Box::new(
+        ctx.fees
+            .estimate()
+            .map_err(ectx!(ErrorKind::Internal))
+            .and_then(|estimates| response_with_model(&estimates)),
+    )
+}
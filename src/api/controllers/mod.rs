@@ -1,18 +1,42 @@
 use std::collections::BTreeMap;
 use std::fmt::{self, Display};
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, Mutex};
 
 use futures::prelude::*;
 use hyper::{header::HeaderValue, Body, HeaderMap, Method, Response, Uri};
 
 use super::error::*;
+use audit::{AuditLog, Origin};
+use broadcast::Broadcaster;
 use client::HttpClient;
 use config::Config;
+use fees::FeeEstimator;
+use indexer::IndexStorage;
 use models::*;
+use networks::Networks;
+use redact;
+use shutdown::Shutdown;
+use telemetry::{SpanContext, Tracer};
+use tracker::TxTracker;
+use watches::WatchService;
 
+mod address;
+mod admin;
+mod broadcasts;
+mod fees;
 mod proxy;
+mod ready;
+mod watches;
 
+pub use self::address::*;
+pub use self::admin::*;
+pub use self::broadcasts::*;
+pub use self::fees::*;
 pub use self::proxy::*;
+pub use self::ready::*;
+pub use self::watches::*;
 
 pub type ControllerFuture = Box<Future<Item = Response<Body>, Error = Error> + Send>;
 
@@ -25,16 +49,47 @@ pub struct Context {
     pub client: Arc<dyn HttpClient>,
     pub config: Arc<Config>,
     pub nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    /// Counter of proxied requests, used to send a share of them to nodes on probation
+    pub requests_counter: Arc<AtomicUsize>,
+    pub networks: Networks,
+    pub index: Option<Arc<IndexStorage>>,
+    pub fees: FeeEstimator,
+    pub broadcaster: Broadcaster,
+    pub tracker: Option<TxTracker>,
+    pub watches: Option<WatchService>,
+    pub audit: Option<AuditLog>,
+    /// SHA-256 fingerprint of client certificate with mutual TLS
+    pub caller: Option<String>,
+    pub remote_addr: Option<SocketAddr>,
+    pub tracer: Tracer,
+    /// Span of the request, parent of spans of its handling
+    pub trace: SpanContext,
+    pub shutdown: Shutdown,
+    /// Correlation id, taken from `X-Request-Id` or generated
+    pub request_id: String,
+}
+
+impl Context {
+    /// Who made the request, for audit records
+    pub fn origin(&self) -> Origin {
+        Origin {
+            request_id: self.request_id.clone(),
+            client: self.remote_addr.map(|addr| addr.to_string()),
+            caller: self.caller.clone(),
+        }
+    }
 }
 
 impl Display for Context {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.write_str(&format!(
-            "{} {}, headers: {:#?}, body: {:?}",
+            "{} {}, request id: {}, caller: {:?}, headers: {:#?}, body: {}",
             self.method,
             self.uri,
-            self.headers,
-            String::from_utf8(self.body.clone()).ok()
+            self.request_id,
+            self.caller,
+            redact::headers(&self.headers),
+            redact::request_body(&self.body)
         ))
     }
 }
@@ -1,30 +1,257 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+
 use failure::Fail;
+use futures::future::{self, Either};
 use futures::prelude::*;
+use hyper::{Body, Response};
+use serde_json;
 
+use super::super::access_log::UpstreamNode;
 use super::super::utils::parse_body;
 use super::Context;
 use super::ControllerFuture;
-use super::ErrorKind;
-use client::{BitcoinClient, BitcoinClientImpl};
+use super::{Error, ErrorContext, ErrorKind, ErrorSource};
+use audit::{self, AuditLog, Origin};
+use broadcast::Broadcaster;
+use client::{BitcoinClient, BitcoinClientErrorKind, BitcoinClientImpl};
+use logger;
 use models::*;
+use networks::Network;
+use redact;
+use telemetry::{SpanContext, SpanKind, Tracer};
+use tracker::TxTracker;
+use utils::read_body;
+
+/// Rpc methods that only read chain or node state, the only ones that may go to nodes on probation.
+/// Wallets are per node, so wallet calls always go to the active node.
+const READ_ONLY_METHODS: &[&str] = &[
+    "decodepsbt",
+    "decoderawtransaction",
+    "decodescript",
+    "estimatesmartfee",
+    "getbestblockhash",
+    "getblock",
+    "getblockchaininfo",
+    "getblockcount",
+    "getblockfilter",
+    "getblockhash",
+    "getblockheader",
+    "getblockstats",
+    "getchaintips",
+    "getchaintxstats",
+    "getconnectioncount",
+    "getdifficulty",
+    "getindexinfo",
+    "getmempoolancestors",
+    "getmempooldescendants",
+    "getmempoolentry",
+    "getmempoolinfo",
+    "getmininginfo",
+    "getnetworkhashps",
+    "getnetworkinfo",
+    "getrawmempool",
+    "getrawtransaction",
+    "gettxout",
+    "gettxoutproof",
+    "gettxoutsetinfo",
+    "testmempoolaccept",
+    "uptime",
+    "validateaddress",
+    "verifytxoutproof",
+];
 
 pub fn proxy(ctx: &Context) -> ControllerFuture {
-    let client = {
-        let mut nodes_ = ctx.nodes.lock().unwrap();
-        //searching for main node not in quarantine
-        let node = nodes_.values().filter(|n| n.main && n.quarantine == Quarantine::No).nth(0).cloned();
-        //if all nodes are in quarantine - take first
-        let node = node.unwrap_or_else(|| {
-            let n = nodes_.get_mut(&0).expect("There is no nodes defined in config");
-            n.main = true;
-            n.clone()
-        });
-
-        BitcoinClientImpl::new(ctx.client.clone(), node.url.clone(), node.user.clone(), node.password.clone())
+    let ctx_clone = ctx.clone();
+    let broadcaster = ctx.broadcaster.clone();
+    let tracker = ctx.tracker.clone();
+    let audit = auditor(ctx);
+    let trace = (ctx.request_id.clone(), ctx.tracer.clone(), ctx.trace.clone());
+    Box::new(check_request(ctx).and_then(move |input| {
+        // transactions are fanned out to all healthy nodes instead of the main one
+        if input["method"] == "sendrawtransaction" {
+            return broadcast_transaction(&broadcaster, tracker, input, trace, audit);
+        }
+        // batched transactions would reach the main node only, without fan-out and tracking
+        let batched_transaction = input
+            .as_array()
+            .map(|requests| requests.iter().any(|request| request["method"] == "sendrawtransaction"))
+            .unwrap_or(false);
+        if batched_transaction {
+            let body = json!({ "description": "sendrawtransaction must be sent as a single request, not in a batch" }).to_string();
+            return Box::new(future::err(
+                ectx!(err ErrorContext::RequestJson, ErrorKind::UnprocessableEntity(body) => redact::rpc_request(&input)),
+            ));
+        }
+        let (client, url) = node_client(&ctx_clone, &ctx_clone.nodes, &ctx_clone.requests_counter, is_read_only(&input));
+        Box::new(logger::with_fields(
+            vec![("node", url.clone())],
+            proxy_to_node(client, url, input, audit),
+        ))
+    }))
+}
+
+/// Proxies json rpc to nodes of additional network. Unlike default nodes, transactions are sent to the active node only.
+pub fn proxy_network(ctx: &Context, network: &Network) -> ControllerFuture {
+    let ctx_clone = ctx.clone();
+    let nodes = network.nodes.clone();
+    let requests_counter = network.requests_counter.clone();
+    let audit = auditor(ctx);
+    Box::new(check_request(ctx).and_then(move |input| {
+        let (client, url) = node_client(&ctx_clone, &nodes, &requests_counter, is_read_only(&input));
+        logger::with_fields(vec![("node", url.clone())], proxy_to_node(client, url, input, audit))
+    }))
+}
+
+/// Whether every call of json rpc request, single or batch, is read-only
+fn is_read_only(input: &serde_json::Value) -> bool {
+    match input {
+        serde_json::Value::Array(requests) => requests.iter().all(is_read_only),
+        request => request["method"]
+            .as_str()
+            .map(|method| READ_ONLY_METHODS.contains(&method))
+            .unwrap_or(false),
+    }
+}
+
+/// Parses json rpc request, tracing whether it's accepted and whether it's audited
+fn check_request(ctx: &Context) -> impl Future<Item = serde_json::Value, Error = Error> + Send {
+    let mut span = ctx.tracer.start_span("check request", SpanKind::Internal, Some(&ctx.trace));
+    parse_body::<serde_json::Value>(ctx.body.clone()).then(move |r| {
+        match r {
+            Ok(ref input) => span.set_attribute("audited", audit::has_audited_calls(input)),
+            Err(ref e) => span.set_error(e),
+        }
+        span.end();
+        r
+    })
+}
+
+/// Proxies request to the node, recording audited calls along with their results
+fn proxy_to_node(client: BitcoinClientImpl, url: String, input: serde_json::Value, audit: Option<(AuditLog, Origin)>) -> ControllerFuture {
+    let input_clone = redact::rpc_request(&input);
+    let (audit, origin) = match audit {
+        Some(audit) if audit::has_audited_calls(&input) => audit,
+        _ => {
+            return Box::new(
+                client
+                    .proxy_request(&input)
+                    .map_err(ectx!(ErrorKind::Internal => input_clone))
+                    .map(move |resp| with_upstream_node(resp, url)),
+            )
+        }
     };
-    let body = ctx.body.clone();
-    Box::new(parse_body::<serde_json::Value>(body).and_then(move |input| {
-        let input_clone = input.clone();
-        client.proxy_request(&input).map_err(ectx!(ErrorKind::Internal => input_clone))
+    Box::new(client.proxy_request(&input).then(move |r| match r {
+        Ok(resp) => {
+            let (parts, body) = resp.into_parts();
+            Either::A(read_body(body).then(move |r| match r {
+                Ok(body) => {
+                    let response = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+                    audit.record_rpc(&origin, Some(&url), &input, &response);
+                    Ok(with_upstream_node(Response::from_parts(parts, body.into()), url))
+                }
+                // node has run the call, only its result is lost
+                Err(e) => {
+                    let error = json!({ "message": format!("Error reading response: {}", e) });
+                    audit.record_rpc(&origin, Some(&url), &input, &json!({ "result": null, "error": error }));
+                    Err(ectx!(err e, ErrorSource::Hyper, ErrorKind::Internal => input_clone))
+                }
+            }))
+        }
+        Err(e) => {
+            let error = match e.kind() {
+                BitcoinClientErrorKind::Rpc(rpc_error) => json!(rpc_error),
+                _ => json!({ "message": e.to_string() }),
+            };
+            audit.record_rpc(&origin, Some(&url), &input, &json!({ "result": null, "error": error }));
+            Either::B(future::err(ectx!(err e, ErrorKind::Internal => input_clone)))
+        }
     }))
 }
+
+/// Audit log with origin of the request, if audit is configured
+fn auditor(ctx: &Context) -> Option<(AuditLog, Origin)> {
+    ctx.audit.clone().map(|audit| (audit, ctx.origin()))
+}
+
+/// Client of the node to proxy to, carrying request id of `ctx`, and url of the node.
+/// Only `read_only` requests may go to nodes on probation.
+fn node_client(
+    ctx: &Context,
+    nodes: &Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    requests_counter: &AtomicUsize,
+    read_only: bool,
+) -> (BitcoinClientImpl, String) {
+    let mut nodes_ = nodes.lock().unwrap();
+    let mut span = ctx.tracer.start_span("select node", SpanKind::Internal, Some(&ctx.trace));
+    let node = if read_only {
+        proxy_node(&mut nodes_, ctx.config.healthcheck.probation.traffic_percent, requests_counter)
+    } else {
+        active_node(&mut nodes_)
+    };
+    span.set_attribute("node.url", node.url.as_str());
+    span.set_attribute("node.probation", node.quarantine != Quarantine::No);
+    span.end();
+
+    let client = BitcoinClientImpl::from_node(ctx.client.clone(), &node)
+        .with_request_id(ctx.request_id.clone())
+        .with_trace(ctx.tracer.clone(), ctx.trace.clone());
+    (client, node.url)
+}
+
+fn with_upstream_node(mut resp: Response<Body>, url: String) -> Response<Body> {
+    resp.extensions_mut().insert(UpstreamNode(url));
+    resp
+}
+
+fn broadcast_transaction(
+    broadcaster: &Broadcaster,
+    tracker: Option<TxTracker>,
+    input: serde_json::Value,
+    (request_id, tracer, parent): (String, Tracer, SpanContext),
+    audit: Option<(AuditLog, Origin)>,
+) -> ControllerFuture {
+    let hex = {
+        let params = &input["params"];
+        params[0]
+            .as_str()
+            .or_else(|| params["hexstring"].as_str())
+            .map(|hex| hex.to_string())
+    };
+    let hex = match hex {
+        Some(hex) => hex,
+        None => return Box::new(future::err(ectx!(err ErrorContext::RequestJson, ErrorKind::BadRequest => input))),
+    };
+    let id = input["id"].clone();
+    let hex_clone = hex.clone();
+    Box::new(
+        broadcaster
+            .broadcast(hex.clone(), input["params"].clone(), request_id, tracer, parent)
+            .map_err(move |_| ectx!(err ErrorContext::Broadcast, ErrorKind::Internal => hex_clone))
+            .map(move |report| {
+                // mimic bitcoind json rpc response, adding per target results
+                let (status, body) = match report.txid {
+                    Some(txid) => {
+                        if let Some(tracker) = tracker {
+                            tracker.track(txid.clone(), hex);
+                        }
+                        (200, json!({ "result": txid, "error": null, "id": id, "broadcast": report.results }))
+                    }
+                    None => (
+                        500,
+                        json!({ "result": null, "error": report.rejection, "id": id, "broadcast": report.results }),
+                    ),
+                };
+                if let Some((audit, origin)) = audit {
+                    audit.record_rpc(&origin, None, &input, &body);
+                }
+                let resp = Response::builder()
+                    .status(status)
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap();
+                with_upstream_node(resp, report.nodes.join(","))
+            }),
+    )
+}
@@ -0,0 +1,17 @@
+use futures::future;
+use hyper::{Body, Response};
+
+use super::Context;
+use super::ControllerFuture;
+
+/// Readiness probe, fails once shutdown started so that no new traffic is sent here
+pub fn get_ready(ctx: &Context) -> ControllerFuture {
+    let (status, ready) = if ctx.shutdown.is_started() { (503, false) } else { (200, true) };
+    Box::new(future::ok(
+        Response::builder()
+            .status(status)
+            .header("Content-Type", "application/json")
+            .body(Body::from(json!({ "ready": ready }).to_string()))
+            .unwrap(),
+    ))
+}
@@ -0,0 +1,109 @@
+use failure::Fail;
+use futures::future;
+use futures::prelude::*;
+use hyper::Uri;
+use uuid::Uuid;
+
+use super::super::utils::{parse_body, response_with_model};
+use super::admin::authorize;
+use super::Context;
+use super::ControllerFuture;
+use super::{Error, ErrorContext, ErrorKind};
+use models::*;
+use watches::WatchService;
+
+pub fn get_watches(ctx: &Context) -> ControllerFuture {
+    match watches_for(ctx) {
+        Ok(watches) => response_with_model(&watches.list()),
+        Err(e) => Box::new(future::err(e)),
+    }
+}
+
+pub fn get_watch(ctx: &Context, id: String) -> ControllerFuture {
+    let watch = watches_for(ctx).and_then(|watches| {
+        parse_id(&id).and_then(|id| {
+            watches
+                .get(id)
+                .ok_or_else(|| ectx!(err ErrorContext::Watch, ErrorKind::NotFound => id))
+        })
+    });
+    match watch {
+        Ok(watch) => response_with_model(&watch),
+        Err(e) => Box::new(future::err(e)),
+    }
+}
+
+pub fn post_watches(ctx: &Context) -> ControllerFuture {
+    let watches = match watches_for(ctx) {
+        Ok(watches) => watches,
+        Err(e) => return Box::new(future::err(e)),
+    };
+    let ctx = ctx.clone();
+    let watches_clone = watches.clone();
+    Box::new(
+        parse_body::<NewWatch>(ctx.body.clone())
+            .and_then(move |new_watch| validate(&ctx, &watches_clone, &new_watch).map(|_| new_watch))
+            .and_then(move |new_watch| response_with_model(&watches.add(new_watch))),
+    )
+}
+
+pub fn delete_watch(ctx: &Context, id: String) -> ControllerFuture {
+    let watch = watches_for(ctx).and_then(|watches| {
+        parse_id(&id).and_then(|id| {
+            watches
+                .remove(id)
+                .ok_or_else(|| ectx!(err ErrorContext::Watch, ErrorKind::NotFound => id))
+        })
+    });
+    match watch {
+        Ok(watch) => response_with_model(&watch),
+        Err(e) => Box::new(future::err(e)),
+    }
+}
+
+fn watches_for(ctx: &Context) -> Result<WatchService, Error> {
+    ctx.watches
+        .clone()
+        .ok_or_else(|| ectx!(err ErrorContext::WatchesDisabled, ErrorKind::NotFound))
+}
+
+/// Host of http or https url
+fn callback_host(url: &str) -> Option<String> {
+    let uri = url.parse::<Uri>().ok()?;
+    match uri.scheme_part().map(|scheme| scheme.as_str()) {
+        Some("http") | Some("https") => uri.host().map(|host| host.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_id(id: &str) -> Result<Uuid, Error> {
+    Uuid::parse_str(id).map_err(ectx!(ErrorContext::Watch, ErrorKind::NotFound => id))
+}
+
+fn validate(ctx: &Context, watches: &WatchService, new_watch: &NewWatch) -> Result<(), Error> {
+    let max_confirmations = watches.max_confirmations();
+    let websocket_enabled = ctx.config.notifications.is_some();
+    let error = if new_watch.address.is_some() == new_watch.txid.is_some() {
+        Some("exactly one of address and txid must be set".to_string())
+    } else if new_watch.confirmations == 0 || new_watch.confirmations > max_confirmations {
+        Some(format!("confirmations must be between 1 and {}", max_confirmations))
+    } else if new_watch.callback_url.is_none() && !websocket_enabled {
+        Some("callbackUrl is required when websocket notifications are disabled".to_string())
+    } else if let Some(ref callback_url) = new_watch.callback_url {
+        match callback_host(callback_url) {
+            Some(ref host) if watches.callback_host_allowed(host) => None,
+            // callbacks to arbitrary hosts, e.g. internal services, are for admins only
+            Some(_) => return authorize(ctx),
+            None => Some("callbackUrl must be an absolute http or https url".to_string()),
+        }
+    } else {
+        None
+    };
+    match error {
+        Some(error) => {
+            let body = json!({ "description": error }).to_string();
+            Err(ectx!(err ErrorContext::Watch, ErrorKind::UnprocessableEntity(body) => new_watch))
+        }
+        None => Ok(()),
+    }
+}
@@ -28,6 +28,10 @@ pub enum ErrorKind {
 pub enum ErrorSource {
     #[fail(display = "controller source - error inside of Hyper library")]
     Hyper,
+    #[fail(display = "controller source - io error")]
+    Io,
+    #[fail(display = "controller source - error inside of TLS library")]
+    Tls,
 }
 
 #[allow(dead_code)]
@@ -53,6 +57,26 @@ pub enum ErrorContext {
     Timestamp,
     #[fail(display = "controller context - error with sign header")]
     Sign,
+    #[fail(display = "controller context - malformed address")]
+    Address,
+    #[fail(display = "controller context - address index is disabled")]
+    IndexDisabled,
+    #[fail(display = "controller context - transaction is not tracked")]
+    Transaction,
+    #[fail(display = "controller context - tracking of broadcasted transactions is disabled")]
+    TrackingDisabled,
+    #[fail(display = "controller context - error with watch")]
+    Watch,
+    #[fail(display = "controller context - watches are disabled")]
+    WatchesDisabled,
+    #[fail(display = "controller context - admin api is disabled")]
+    Admin,
+    #[fail(display = "controller context - error loading TLS certificates")]
+    Tls,
+    #[fail(display = "controller context - audit log is disabled or unreadable")]
+    Audit,
+    #[fail(display = "controller context - error broadcasting transaction")]
+    Broadcast,
 }
 
 derive_error_impls!();
@@ -1,26 +1,62 @@
+mod access_log;
 mod controllers;
 mod error;
+mod tls;
 mod utils;
 
 use std::collections::BTreeMap;
 use std::net::SocketAddr;
+use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use failure::{Compat, Fail};
-use futures::future;
+use futures::future::{self, Either};
 use futures::prelude::*;
 use futures_cpupool::CpuPool;
 use hyper;
+use hyper::body::Payload;
+use hyper::header::{HeaderValue, HOST};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, Service};
 use hyper::Server;
-use hyper::{service::Service, Body, Request, Response};
+use hyper::{Body, Method, Request, Response};
+use sentry;
 
+use self::access_log::{AccessLogEntry, UpstreamNode};
 use self::controllers::*;
 use self::error::*;
-use super::config::Config;
+use self::tls::TlsConfig;
+use super::config::{Config, ServerTls};
 use super::utils::{log_and_capture_error, log_error, log_warn};
-use client::{HttpClient, HttpClientImpl};
+use audit::AuditLog;
+use broadcast::Broadcaster;
+use client::{HttpClient, HttpClientImpl, REQUEST_ID_HEADER};
+use fees::FeeEstimator;
+use indexer::IndexStorage;
+use logger;
 use models::*;
+use networks::Networks;
+use redact;
+use shutdown::Shutdown;
+use telemetry::{self, SpanContext, SpanKind, Tracer, TRACEPARENT_HEADER};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+use tokio::timer::Delay;
+use tokio_rustls::server::TlsStream;
+use tracker::TxTracker;
 use utils::read_body;
+use watches::WatchService;
+
+/// Shared state of background subsystems served by the api
+pub struct Services {
+    pub nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    pub networks: Networks,
+    pub index: Option<Arc<IndexStorage>>,
+    pub tracker: Option<TxTracker>,
+    pub watches: Option<WatchService>,
+    pub audit: Option<AuditLog>,
+}
 
 #[derive(Clone)]
 pub struct ApiService {
@@ -29,11 +65,35 @@ pub struct ApiService {
     cpu_pool: CpuPool,
     client: Arc<dyn HttpClient>,
     nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    requests_counter: Arc<AtomicUsize>,
+    networks: Networks,
+    index: Option<Arc<IndexStorage>>,
+    fees: FeeEstimator,
+    broadcaster: Broadcaster,
+    tracker: Option<TxTracker>,
+    watches: Option<WatchService>,
+    audit: Option<AuditLog>,
+    tracer: Tracer,
+    shutdown: Shutdown,
+    /// Identity of the caller, set per connection with mutual TLS
+    caller: Option<String>,
+    /// Address of the connected client, set per connection
+    remote_addr: Option<SocketAddr>,
 }
 
 impl ApiService {
-    fn from_config(config: Config, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>) -> Result<Self, Error> {
-        let client = HttpClientImpl::new(&config);
+    fn from_config(config: Config, services: Services, shutdown: Shutdown) -> Result<Self, Error> {
+        let Services {
+            nodes,
+            networks,
+            index,
+            tracker,
+            watches,
+            audit,
+        } = services;
+        let client: Arc<dyn HttpClient> = Arc::new(HttpClientImpl::new(&config));
+        let fees = FeeEstimator::new(&config, client.clone(), nodes.clone());
+        let broadcaster = Broadcaster::new(&config, client.clone(), nodes.clone());
         let host = config.server.host.clone();
         let port = config.server.port.clone();
         let server_address = format!("{}:{}", host, port).parse::<SocketAddr>().map_err(ectx!(try
@@ -43,12 +103,26 @@ impl ApiService {
             port
         ))?;
         let cpu_pool = CpuPool::new(config.cpu_pool.size);
+        let tracer = Tracer::new(config.telemetry.clone());
+        telemetry::start(tracer.clone(), HttpClientImpl::new(&config));
         Ok(ApiService {
             config: Arc::new(config),
             server_address,
             cpu_pool,
-            client: Arc::new(client),
+            client,
             nodes,
+            requests_counter: Arc::new(AtomicUsize::new(0)),
+            networks,
+            index,
+            fees,
+            broadcaster,
+            tracker,
+            watches,
+            audit,
+            tracer,
+            shutdown,
+            caller: None,
+            remote_addr: None,
         })
     }
 }
@@ -64,103 +138,248 @@ impl Service for ApiService {
         let client = self.client.clone();
         let config = self.config.clone();
         let nodes = self.nodes.clone();
+        let requests_counter = self.requests_counter.clone();
+        let networks = self.networks.clone();
+        let index = self.index.clone();
+        let fees = self.fees.clone();
+        let broadcaster = self.broadcaster.clone();
+        let tracker = self.tracker.clone();
+        let watches = self.watches.clone();
+        let audit = self.audit.clone();
+        let caller = self.caller.clone();
+        let remote_addr = self.remote_addr;
+        let request_id = access_log::request_id(&parts.headers);
+        let parent = parts
+            .headers
+            .get(TRACEPARENT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(SpanContext::from_traceparent);
+        let mut span = self.tracer.start_span(parts.method.as_str(), SpanKind::Server, parent.as_ref());
+        span.set_attribute("http.method", parts.method.as_str());
+        span.set_attribute("http.target", parts.uri.path());
+        span.set_attribute("request_id", request_id.as_str());
+        let tracer = self.tracer.clone();
+        let shutdown = self.shutdown.clone();
+        let trace = span.context().clone();
+        let mut entry = AccessLogEntry::new(request_id.clone(), self.remote_addr, caller.clone(), &parts.method, &parts.uri);
+
+        // request id is added to every record logged while handling the request
+        let log_fields = vec![("request_id", request_id.clone())];
 
-        Box::new(
+        Box::new(logger::with_fields(
+            log_fields,
             read_body(http_body)
                 .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal))
-                .and_then(move |body| {
-                    let ctx = Context {
-                        body,
-                        method: parts.method.clone(),
-                        uri: parts.uri.clone(),
-                        headers: parts.headers,
-                        client,
-                        config,
-                        nodes,
+                .then(move |body| {
+                    let mut log_fields = vec![];
+                    let resp = match body {
+                        Ok(body) => {
+                            entry.request_bytes = body.len();
+                            entry.rpc_method = access_log::rpc_method(&body);
+                            if let Some(ref rpc_method) = entry.rpc_method {
+                                span.set_attribute("rpc.method", rpc_method.as_str());
+                                log_fields.push(("rpc_method", rpc_method.clone()));
+                            }
+                            let ctx = Context {
+                                body,
+                                method: parts.method.clone(),
+                                uri: parts.uri.clone(),
+                                headers: parts.headers,
+                                client,
+                                config,
+                                nodes,
+                                requests_counter,
+                                networks,
+                                index,
+                                fees,
+                                broadcaster,
+                                tracker,
+                                watches,
+                                audit,
+                                caller,
+                                remote_addr,
+                                tracer,
+                                trace,
+                                shutdown,
+                                request_id: request_id.clone(),
+                            };
+
+                            let request_body = ctx.body.clone();
+                            Either::A(
+                                future::lazy(move || {
+                                    debug!("Received request {}", ctx);
+                                    route(&ctx)
+                                })
+                                .and_then(|resp| {
+                                    let (parts, body) = resp.into_parts();
+                                    read_body(body)
+                                        .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal))
+                                        .map(|body| (parts, body))
+                                })
+                                .map(move |(parts, body)| {
+                                    debug!(
+                                        "Sent response with status {}, headers: {:#?}, body: {}",
+                                        parts.status.as_u16(),
+                                        redact::headers(&parts.headers),
+                                        redact::response_body(&request_body, &body)
+                                    );
+                                    Response::from_parts(parts, body.into())
+                                }),
+                            )
+                        }
+                        Err(e) => Either::B(future::err(e)),
                     };
+                    logger::with_fields(
+                        log_fields,
+                        resp.then(move |r| {
+                            let mut resp = r.unwrap_or_else(|e| error_response(e, &request_id));
+                            entry.status = resp.status().as_u16();
+                            entry.node = resp.extensions().get::<UpstreamNode>().map(|node| node.0.clone());
+                            entry.response_bytes = resp.body().content_length();
+                            span.set_attribute("http.status_code", entry.status);
+                            entry.log();
+                            if resp.status().is_server_error() {
+                                span.set_error(resp.status());
+                            }
+                            span.end();
+                            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                                resp.headers_mut().insert(REQUEST_ID_HEADER, value);
+                            }
+                            Ok(resp)
+                        }),
+                    )
+                }),
+        ))
+    }
+}
 
-                    debug!("Received request {}", ctx);
+/// Maps error to response, internal errors are reported to sentry tagged with `request_id`
+fn error_response(e: Error, request_id: &str) -> Response<Body> {
+    let (status, body) = match e.kind() {
+        ErrorKind::BadRequest => {
+            log_error(&e);
+            (400, r#"{"description": "Bad request"}"#.to_string())
+        }
+        ErrorKind::Unauthorized => {
+            log_warn(&e);
+            (401, r#"{"description": "Unauthorized"}"#.to_string())
+        }
+        ErrorKind::NotFound => {
+            log_warn(&e);
+            (404, r#"{"description": "Not found"}"#.to_string())
+        }
+        ErrorKind::UnprocessableEntity(errors) => {
+            log_warn(&e);
+            (422, errors)
+        }
+        ErrorKind::Internal => {
+            sentry::with_scope(|scope| scope.set_tag("request_id", request_id), || log_and_capture_error(e));
+            (500, r#"{"description": "Internal server error"}"#.to_string())
+        }
+    };
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
 
-                    proxy(&ctx)
-                })
-                .and_then(|resp| {
-                    let (parts, body) = resp.into_parts();
-                    read_body(body)
-                        .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal))
-                        .map(|body| (parts, body))
-                })
-                .map(|(parts, body)| {
-                    debug!(
-                        "Sent response with status {}, headers: {:#?}, body: {:?}",
-                        parts.status.as_u16(),
-                        parts.headers,
-                        String::from_utf8(body.clone()).ok()
-                    );
-                    Response::from_parts(parts, body.into())
-                })
-                .or_else(|e| match e.kind() {
-                    ErrorKind::BadRequest => {
-                        log_error(&e);
-                        Ok(Response::builder()
-                            .status(400)
-                            .header("Content-Type", "application/json")
-                            .body(Body::from(r#"{"description": "Bad request"}"#))
-                            .unwrap())
-                    }
-                    ErrorKind::Unauthorized => {
-                        log_warn(&e);
-                        Ok(Response::builder()
-                            .status(401)
-                            .header("Content-Type", "application/json")
-                            .body(Body::from(r#"{"description": "Unauthorized"}"#))
-                            .unwrap())
-                    }
-                    ErrorKind::NotFound => {
-                        log_warn(&e);
-                        Ok(Response::builder()
-                            .status(404)
-                            .header("Content-Type", "application/json")
-                            .body(Body::from(r#"{"description": "Not found"}"#))
-                            .unwrap())
-                    }
-                    ErrorKind::UnprocessableEntity(errors) => {
-                        log_warn(&e);
-                        Ok(Response::builder()
-                            .status(422)
-                            .header("Content-Type", "application/json")
-                            .body(Body::from(errors))
-                            .unwrap())
-                    }
-                    ErrorKind::Internal => {
-                        log_and_capture_error(e);
-                        Ok(Response::builder()
-                            .status(500)
-                            .header("Content-Type", "application/json")
-                            .body(Body::from(r#"{"description": "Internal server error"}"#))
-                            .unwrap())
-                    }
-                }),
-        )
+fn route(ctx: &Context) -> ControllerFuture {
+    let path = ctx.uri.path().to_string();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let host = ctx.headers.get(HOST).and_then(|host| host.to_str().ok()).unwrap_or_default();
+    // requests to additional networks are json rpc only
+    if let Some(network) = ctx.networks.by_name(segments[0]).or_else(|| ctx.networks.by_host(host)) {
+        return proxy_network(ctx, network);
+    }
+    match (&ctx.method, &segments[..]) {
+        (&Method::GET, ["api", "v1", "ready"]) => get_ready(ctx),
+        (&Method::GET, ["api", "v1", "address", address, "utxo"]) => get_address_utxo(ctx, address.to_string()),
+        (&Method::GET, ["api", "v1", "address", address, "balance"]) => get_address_balance(ctx, address.to_string()),
+        (&Method::GET, ["api", "v1", "address", address, "txs"]) => get_address_txs(ctx, address.to_string()),
+        (&Method::GET, ["api", "v1", "fees"]) => get_fees(ctx),
+        (&Method::GET, ["api", "v1", "broadcasts"]) => get_broadcasts(ctx),
+        (&Method::GET, ["api", "v1", "broadcasts", txid]) => get_broadcast(ctx, txid.to_string()),
+        (&Method::GET, ["api", "v1", "admin", "nodes"]) => get_admin_nodes(ctx),
+        (&Method::GET, ["api", "v1", "admin", "audit"]) => get_admin_audit(ctx),
+        (&Method::GET, ["api", "v1", "admin", "log"]) => get_admin_log(ctx),
+        (&Method::PUT, ["api", "v1", "admin", "log"]) => put_admin_log(ctx),
+        (&Method::GET, ["api", "v1", "watches"]) => get_watches(ctx),
+        (&Method::POST, ["api", "v1", "watches"]) => post_watches(ctx),
+        (&Method::GET, ["api", "v1", "watches", id]) => get_watch(ctx, id.to_string()),
+        (&Method::DELETE, ["api", "v1", "watches", id]) => delete_watch(ctx, id.to_string()),
+        // everything else is json rpc for bitcoind
+        _ => proxy(ctx),
     }
 }
 
-pub fn start_server(config: Config, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>) {
-    hyper::rt::run(future::lazy(move || {
-        ApiService::from_config(config, nodes)
+/// Serves api until shutdown is drained after `server.shutdown_delay`, then waits for in-flight requests
+/// up to `server.shutdown_timeout`
+pub fn start_server(config: Config, services: Services, shutdown: Shutdown) {
+    let timeout = Duration::from_secs(config.server.shutdown_timeout);
+    let deadline = shutdown.drain().and_then(move |_| {
+        Delay::new(Instant::now() + timeout).then(move |_| {
+            warn!("Requests are not finished in {} seconds, closing connections", timeout.as_secs());
+            Ok(())
+        })
+    });
+    let server = future::lazy(move || {
+        hyper::rt::spawn(shutdown.listen());
+        ApiService::from_config(config, services, shutdown)
             .into_future()
-            .and_then(move |api| {
+            .and_then(move |api| -> Box<Future<Item = (), Error = Error> + Send> {
+                if let Some(tls_config) = api.config.server.tls.clone() {
+                    return serve_tls(api, tls_config);
+                }
                 let api_clone = api.clone();
-                let new_service = move || {
-                    let res: Result<_, hyper::Error> = Ok(api_clone.clone());
+                let new_service = make_service_fn(move |conn: &AddrStream| {
+                    let mut api = api_clone.clone();
+                    api.remote_addr = Some(conn.remote_addr());
+                    let res: Result<_, hyper::Error> = Ok(api);
                     res
-                };
+                });
                 let addr = api.server_address.clone();
                 let server = Server::bind(&api.server_address)
                     .serve(new_service)
+                    .with_graceful_shutdown(api.shutdown.drain())
                     .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal => addr));
                 info!("Listening on http://{}", addr);
-                server
+                Box::new(server)
             })
             .map_err(|e: Error| log_error(&e))
-    }));
+    });
+    let mut runtime = Runtime::new().unwrap_or_else(|e| panic!("Error creating runtime: {}", e));
+    let _ = runtime.block_on(server.select2(deadline).then(|_| -> Result<(), ()> { Ok(()) }));
+    // connections left after deadline are dropped along with the runtime
+    let _ = runtime.shutdown_now().wait();
+    info!("Stopped api server");
+}
+
+/// Serves https, with mutual TLS caller of each connection is identified by its certificate
+fn serve_tls(api: ApiService, tls_config: ServerTls) -> Box<Future<Item = (), Error = Error> + Send> {
+    let addr = api.server_address;
+    let tls = match TlsConfig::new(tls_config) {
+        Ok(tls) => tls,
+        Err(e) => return Box::new(future::err(e)),
+    };
+    let listener = match TcpListener::bind(&addr) {
+        Ok(listener) => listener,
+        Err(e) => return Box::new(future::err(ectx!(err e, ErrorSource::Io, ErrorKind::Internal => addr))),
+    };
+    tls.start_reload();
+    let shutdown = api.shutdown.drain();
+    let new_service = make_service_fn(move |stream: &TlsStream<TcpStream>| {
+        let mut api = api.clone();
+        api.caller = tls::caller(stream);
+        api.remote_addr = stream.get_ref().0.peer_addr().ok();
+        let res: Result<_, hyper::Error> = Ok(api);
+        res
+    });
+    info!("Listening on https://{}", addr);
+    Box::new(
+        Server::builder(tls.incoming(listener))
+            .serve(new_service)
+            .with_graceful_shutdown(shutdown)
+            .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal => addr)),
+    )
 }
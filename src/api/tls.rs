@@ -0,0 +1,132 @@
+//! TLS termination for the api server, with optional verification of client certificates.
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use failure::Fail;
+use futures::prelude::*;
+use hex;
+use sha2::{Digest, Sha256};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::timer::Timeout;
+use tokio_rustls::rustls::internal::pemfile;
+use tokio_rustls::rustls::{AllowAnyAuthenticatedClient, NoClientAuth, RootCertStore, ServerConfig, Session};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use super::error::*;
+use config::ServerTls;
+use utils::log_error;
+
+/// Maximum number of TLS handshakes in progress at once
+const MAX_HANDSHAKES: usize = 100;
+/// Connections that didn't finish TLS handshake in time are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Server TLS config, rebuilt from certificate files every `reload_interval`,
+/// so that renewed certificates are picked up without restart
+#[derive(Clone)]
+pub struct TlsConfig {
+    config: ServerTls,
+    current: Arc<RwLock<Arc<ServerConfig>>>,
+}
+
+impl TlsConfig {
+    pub fn new(config: ServerTls) -> Result<Self, Error> {
+        let current = load(&config)?;
+        Ok(Self {
+            config,
+            current: Arc::new(RwLock::new(Arc::new(current))),
+        })
+    }
+
+    /// Spawns a thread reloading certificate files, failed reload keeps the previous certificates
+    pub fn start_reload(&self) {
+        let interval = match self.config.reload_interval {
+            Some(interval) => Duration::from_secs(interval),
+            None => return,
+        };
+        let self_clone = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            match load(&self_clone.config) {
+                Ok(config) => *self_clone.current.write().unwrap() = Arc::new(config),
+                Err(e) => log_error(&e),
+            }
+        });
+    }
+
+    /// Accepted connections with finished TLS handshake, failed connections are logged and skipped
+    pub fn incoming(&self, listener: TcpListener) -> impl Stream<Item = TlsStream<TcpStream>, Error = io::Error> + Send {
+        let self_clone = self.clone();
+        listener
+            .incoming()
+            .then(|r| match r {
+                Ok(stream) => Ok(Some(stream)),
+                Err(e) => {
+                    warn!("Error accepting connection: {}", e);
+                    Ok(None)
+                }
+            })
+            .filter_map(|stream| stream)
+            .map(move |stream| {
+                let acceptor = TlsAcceptor::from(self_clone.current.read().unwrap().clone());
+                Timeout::new(acceptor.accept(stream), HANDSHAKE_TIMEOUT).then(|r| match r {
+                    Ok(stream) => Ok(Some(stream)),
+                    Err(e) => {
+                        warn!("TLS handshake failed: {}", e);
+                        Ok(None)
+                    }
+                })
+            })
+            .buffer_unordered(MAX_HANDSHAKES)
+            .filter_map(|stream| stream)
+    }
+}
+
+/// Identity of the caller with mutual TLS: hex encoded SHA-256 fingerprint of client certificate
+pub fn caller(stream: &TlsStream<TcpStream>) -> Option<String> {
+    let (_, session) = stream.get_ref();
+    session
+        .get_peer_certificates()
+        .and_then(|certs| certs.into_iter().next())
+        .map(|cert| hex::encode(Sha256::digest(&cert.0)))
+}
+
+fn load(config: &ServerTls) -> Result<ServerConfig, Error> {
+    let verifier = match config.client_ca_path {
+        Some(ref path) => {
+            let mut roots = RootCertStore::empty();
+            roots
+                .add_pem_file(&mut open(path)?)
+                .map_err(|_| ectx!(try err ErrorContext::Tls, ErrorKind::Internal => path))?;
+            AllowAnyAuthenticatedClient::new(roots)
+        }
+        None => NoClientAuth::new(),
+    };
+    let certs = pemfile::certs(&mut open(&config.cert_path)?)
+        .map_err(|_| ectx!(try err ErrorContext::Tls, ErrorKind::Internal => config.cert_path))?;
+    let key = pemfile::pkcs8_private_keys(&mut open(&config.key_path)?)
+        .ok()
+        .and_then(|keys| keys.into_iter().next())
+        .or_else(|| {
+            pemfile::rsa_private_keys(&mut open(&config.key_path).ok()?)
+                .ok()
+                .and_then(|keys| keys.into_iter().next())
+        })
+        .ok_or_else(|| ectx!(try err ErrorContext::Tls, ErrorKind::Internal => config.key_path))?;
+    let mut server_config = ServerConfig::new(verifier);
+    server_config
+        .set_single_cert(certs, key)
+        .map_err(ectx!(try ErrorContext::Tls, ErrorSource::Tls, ErrorKind::Internal => config.cert_path, config.key_path))?;
+    Ok(server_config)
+}
+
+fn open(path: &str) -> Result<BufReader<File>, Error> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(ectx!(ErrorContext::Tls, ErrorSource::Io, ErrorKind::Internal => path))
+}
@@ -1,8 +1,10 @@
 use failure::Fail;
 use futures::prelude::*;
-use serde::Deserialize;
+use hyper::Response;
+use serde::{Deserialize, Serialize};
 use serde_json;
 
+use super::controllers::ControllerFuture;
 use super::error::*;
 
 pub fn parse_body<T>(body: Vec<u8>) -> impl Future<Item = T, Error = Error> + Send
@@ -14,3 +16,21 @@ where
         .into_future()
         .and_then(|string| serde_json::from_str::<T>(&string).map_err(ectx!(ErrorContext::RequestJson, ErrorKind::BadRequest => string)))
 }
+
+pub fn response_with_model<M>(model: &M) -> ControllerFuture
+where
+    M: Serialize,
+{
+    Box::new(
+        serde_json::to_string(model)
+            .map_err(ectx!(ErrorContext::ResponseJson, ErrorKind::Internal))
+            .into_future()
+            .map(|text| {
+                Response::builder()
+                    .status(200)
+                    .header("Content-Type", "application/json")
+                    .body(text.into())
+                    .unwrap()
+            }),
+    )
+}
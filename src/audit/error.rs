@@ -0,0 +1,35 @@
+use failure::{Backtrace, Context, Fail};
+use std::fmt;
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "audit error - internal error")]
+    Internal,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorSource {
+    #[fail(display = "audit source - io error")]
+    Io,
+    #[fail(display = "audit source - error serializing or parsing json")]
+    Json,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorContext {
+    #[fail(display = "audit context - error reading audit log")]
+    Read,
+    #[fail(display = "audit context - error writing audit log")]
+    Write,
+}
+
+derive_error_impls!();
@@ -0,0 +1,345 @@
+//! Append-only log of wallet-affecting rpc calls. Each record carries hash of the previous one,
+//! so that removed or edited records break the chain.
+
+mod error;
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+use chrono::{self, NaiveDateTime};
+use failure::Fail;
+use futures::Future;
+use futures_cpupool::CpuPool;
+use hex;
+use serde_json::{self, Value};
+use sha2::{Digest, Sha256};
+
+use self::error::*;
+use config::Audit as AuditConfig;
+use redact;
+
+/// Rpc methods that move funds or change wallets
+const AUDITED_METHODS: &[&str] = &[
+    "abandontransaction",
+    "backupwallet",
+    "bumpfee",
+    "createwallet",
+    "encryptwallet",
+    "importaddress",
+    "importdescriptors",
+    "importmulti",
+    "importprivkey",
+    "importpubkey",
+    "importwallet",
+    "loadwallet",
+    "lockunspent",
+    "psbtbumpfee",
+    "send",
+    "sendall",
+    "sendmany",
+    "sendrawtransaction",
+    "sendtoaddress",
+    "sethdseed",
+    "settxfee",
+    "unloadwallet",
+    "walletlock",
+    "walletpassphrase",
+    "walletpassphrasechange",
+];
+
+/// Rpc methods returning id of the sent transaction, as a string or in `txid` field
+const TXID_METHODS: &[&str] = &["bumpfee", "send", "sendall", "sendmany", "sendrawtransaction", "sendtoaddress"];
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Who made a request, attached to its audit records
+#[derive(Debug, Clone)]
+pub struct Origin {
+    pub request_id: String,
+    /// Remote address of the client
+    pub client: Option<String>,
+    /// Client certificate fingerprint with mutual TLS
+    pub caller: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    pub seq: u64,
+    pub time: NaiveDateTime,
+    pub request_id: String,
+    pub client: Option<String>,
+    pub caller: Option<String>,
+    /// Node that served the call, `None` for transactions broadcasted to all nodes
+    pub node: Option<String>,
+    pub method: String,
+    /// Params with secrets masked
+    pub params: Value,
+    pub txid: Option<String>,
+    pub error: Option<Value>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Hex sha256 of the record serialized with empty `hash`
+    fn digest(&self) -> String {
+        let mut record = self.clone();
+        record.hash = String::new();
+        let data = serde_json::to_vec(&record).unwrap_or_default();
+        hex::encode(Sha256::digest(&data))
+    }
+}
+
+/// Result of checking the hash chain of audit log
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditVerification {
+    pub records: u64,
+    pub valid: bool,
+    /// Line number of the first record that doesn't match the chain
+    pub broken_at: Option<u64>,
+    /// Line numbers of incomplete records, e.g. cut short by a crash while writing. The chain continues past them.
+    pub torn_lines: Vec<u64>,
+}
+
+/// Audited call of a request waiting to be written
+struct Call {
+    method: String,
+    params: Value,
+    txid: Option<String>,
+    error: Option<Value>,
+}
+
+struct Chain {
+    file: File,
+    seq: u64,
+    last_hash: String,
+}
+
+/// Records are written and synced on a separate thread, so that rpc responses don't wait for disk
+#[derive(Clone)]
+pub struct AuditLog {
+    path: String,
+    chain: Arc<Mutex<Chain>>,
+    cpu_pool: CpuPool,
+}
+
+impl AuditLog {
+    /// Opens log for appending, continuing the chain from its last complete record
+    pub fn open(config: &AuditConfig) -> Result<Self, Error> {
+        let path = config.path.clone();
+        let verification = verify(&path)?;
+        if !verification.valid {
+            error!(
+                "Audit log {} is tampered with, chain is broken at record {:?}",
+                path, verification.broken_at
+            );
+        }
+        if !verification.torn_lines.is_empty() {
+            warn!("Audit log {} has incomplete records at lines {:?}", path, verification.torn_lines);
+        }
+        let last = last_record(&path)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&config.path)
+            .map_err(ectx!(try ErrorContext::Write, ErrorSource::Io, ErrorKind::Internal => config.path))?;
+        // record cut short by a crash is left on its own line
+        if !ends_with_newline(&mut file) {
+            file.write_all(b"\n")
+                .map_err(ectx!(try ErrorContext::Write, ErrorSource::Io, ErrorKind::Internal => config.path))?;
+        }
+        let chain = Chain {
+            file,
+            seq: last.as_ref().map(|record| record.seq).unwrap_or(0),
+            last_hash: last.map(|record| record.hash).unwrap_or_else(|| GENESIS_HASH.to_string()),
+        };
+        Ok(Self {
+            path,
+            chain: Arc::new(Mutex::new(chain)),
+            cpu_pool: CpuPool::new(1),
+        })
+    }
+
+    pub fn verify(&self) -> Result<AuditVerification, Error> {
+        // holding the lock so that no record is half written while reading
+        let _chain = self.chain.lock().unwrap();
+        verify(&self.path)
+    }
+
+    /// Records audited calls of json rpc request, single or batch, with their results from response
+    pub fn record_rpc(&self, origin: &Origin, node: Option<&str>, request: &Value, response: &Value) {
+        let calls: Vec<(&Value, &Value)> = match (request, response) {
+            (Value::Array(requests), Value::Array(responses)) => requests
+                .iter()
+                .map(|request| {
+                    let response = responses
+                        .iter()
+                        .find(|response| response["id"] == request["id"])
+                        .unwrap_or(&Value::Null);
+                    (request, response)
+                })
+                .collect(),
+            // node failed the whole batch
+            (Value::Array(requests), response) => requests.iter().map(|request| (request, response)).collect(),
+            (request, response) => vec![(request, response)],
+        };
+        let mut audited = vec![];
+        for (request, response) in calls {
+            let method = match request["method"].as_str() {
+                Some(method) if is_audited(method) => method,
+                _ => continue,
+            };
+            let txid = if TXID_METHODS.contains(&method) {
+                let result = &response["result"];
+                result.as_str().or_else(|| result["txid"].as_str()).map(|txid| txid.to_string())
+            } else {
+                None
+            };
+            let error = match response["error"] {
+                Value::Null => None,
+                ref error => Some(error.clone()),
+            };
+            audited.push(Call {
+                method: method.to_string(),
+                params: request["params"].clone(),
+                txid,
+                error,
+            });
+        }
+        if audited.is_empty() {
+            return;
+        }
+        // single thread of the pool keeps records in order of calls
+        let self_clone = self.clone();
+        let origin = origin.clone();
+        let node = node.map(|node| node.to_string());
+        self.cpu_pool
+            .spawn_fn(move || -> Result<(), ()> {
+                for call in audited {
+                    if let Err(e) = self_clone.append(&origin, node.as_ref().map(|node| node.as_str()), &call) {
+                        error!("Couldn't write audit record of {} call {}: {}", call.method, origin.request_id, e);
+                    }
+                }
+                Ok(())
+            })
+            .forget();
+    }
+
+    /// Waits until records of calls made so far are written
+    pub fn flush(&self) {
+        let _ = self.cpu_pool.spawn_fn(|| Ok::<(), ()>(())).wait();
+    }
+
+    fn append(&self, origin: &Origin, node: Option<&str>, call: &Call) -> Result<(), Error> {
+        let mut chain = self.chain.lock().unwrap();
+        let request = json!({ "method": call.method, "params": call.params });
+        let mut record = AuditRecord {
+            seq: chain.seq + 1,
+            time: chrono::Utc::now().naive_utc(),
+            request_id: origin.request_id.clone(),
+            client: origin.client.clone(),
+            caller: origin.caller.clone(),
+            node: node.map(|node| node.to_string()),
+            method: call.method.clone(),
+            params: redact::rpc_request(&request)["params"].clone(),
+            txid: call.txid.clone(),
+            error: call.error.clone(),
+            prev_hash: chain.last_hash.clone(),
+            hash: String::new(),
+        };
+        record.hash = record.digest();
+        let mut line = serde_json::to_vec(&record).map_err(ectx!(try ErrorContext::Write, ErrorSource::Json, ErrorKind::Internal))?;
+        line.push(b'\n');
+        let path = self.path.clone();
+        chain
+            .file
+            .write_all(&line)
+            .and_then(|_| chain.file.sync_data())
+            .map_err(ectx!(try ErrorContext::Write, ErrorSource::Io, ErrorKind::Internal => path))?;
+        chain.seq = record.seq;
+        chain.last_hash = record.hash;
+        Ok(())
+    }
+}
+
+/// Whether json rpc request, single or batch, has calls to audited methods
+pub fn has_audited_calls(request: &Value) -> bool {
+    match request {
+        Value::Array(requests) => requests.iter().any(has_audited_calls),
+        request => request["method"].as_str().map(is_audited).unwrap_or(false),
+    }
+}
+
+fn is_audited(method: &str) -> bool {
+    AUDITED_METHODS.contains(&method)
+}
+
+/// Checks that every record is intact and points to the previous one
+pub fn verify(path: &str) -> Result<AuditVerification, Error> {
+    let mut verification = AuditVerification {
+        records: 0,
+        valid: true,
+        broken_at: None,
+        torn_lines: vec![],
+    };
+    let mut last_hash = GENESIS_HASH.to_string();
+    for (i, line) in lines(path)?.into_iter().enumerate() {
+        let line_number = i as u64 + 1;
+        // replacing a record with garbage still breaks the chain at the next one
+        let record = match serde_json::from_str::<AuditRecord>(&line) {
+            Ok(record) => record,
+            Err(_) => {
+                verification.torn_lines.push(line_number);
+                continue;
+            }
+        };
+        verification.records += 1;
+        let intact = record.prev_hash == last_hash && record.digest() == record.hash;
+        last_hash = record.hash;
+        if !intact && verification.valid {
+            verification.valid = false;
+            verification.broken_at = Some(line_number);
+        }
+    }
+    Ok(verification)
+}
+
+/// Last complete record, incomplete ones after it are skipped
+fn last_record(path: &str) -> Result<Option<AuditRecord>, Error> {
+    Ok(lines(path)?
+        .into_iter()
+        .rev()
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .next())
+}
+
+/// Whether file is empty or its last byte is a line break
+fn ends_with_newline(file: &mut File) -> bool {
+    let mut last = [0u8];
+    match file.seek(SeekFrom::End(-1)) {
+        Ok(_) => file.read_exact(&mut last).map(|_| last[0] == b'\n').unwrap_or(true),
+        // seeking before start fails for empty file
+        Err(_) => true,
+    }
+}
+
+/// Non-empty lines of the log, none if it doesn't exist yet
+fn lines(path: &str) -> Result<Vec<String>, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == ::std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(ectx!(err e, ErrorContext::Read, ErrorSource::Io, ErrorKind::Internal => path)),
+    };
+    let mut lines = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(ectx!(try ErrorContext::Read, ErrorSource::Io, ErrorKind::Internal => path))?;
+        if !line.trim().is_empty() {
+            lines.push(line);
+        }
+    }
+    Ok(lines)
+}
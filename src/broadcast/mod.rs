@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use futures::future;
+use futures::prelude::*;
+
+use client::{BitcoinClient, BitcoinClientErrorKind, BitcoinClientImpl, HttpClient, PushTxClient, PushTxClientImpl};
+use config::Config;
+use models::*;
+use telemetry::{SpanContext, Tracer};
+use utils::log_warn;
+
+/// `RPC_MISC_ERROR` in bitcoind, used for failures that are not rpc rejections
+const MISC_ERROR_CODE: i64 = -1;
+
+/// Sends transactions to every healthy node and configured push APIs
+#[derive(Clone)]
+pub struct Broadcaster {
+    http_client: Arc<HttpClient>,
+    nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    push_clients: Vec<PushTxClientImpl>,
+}
+
+impl Broadcaster {
+    pub fn new(config: &Config, http_client: Arc<HttpClient>, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>) -> Self {
+        let push_clients = config
+            .broadcast
+            .push_urls
+            .iter()
+            .map(|url| PushTxClientImpl::new(http_client.clone(), url.clone()))
+            .collect();
+        Self {
+            http_client,
+            nodes,
+            push_clients,
+        }
+    }
+
+    /// Broadcasts transaction with `sendrawtransaction` params as sent by caller, passing `request_id` to nodes
+    /// and tracing calls to them as children of `parent`.
+    /// Never fails, all errors are reported per target in `BroadcastReport`.
+    pub fn broadcast(
+        &self,
+        hex: String,
+        params: ::serde_json::Value,
+        request_id: String,
+        tracer: Tracer,
+        parent: SpanContext,
+    ) -> Box<Future<Item = BroadcastReport, Error = ()> + Send> {
+        let nodes = {
+            let mut nodes = self.nodes.lock().unwrap();
+            let healthy = healthy_nodes(&nodes);
+            if healthy.is_empty() {
+                vec![active_node(&mut nodes)]
+            } else {
+                healthy
+            }
+        };
+        let node_urls: Vec<String> = nodes.iter().map(|node| node.url.clone()).collect();
+        let mut results: Vec<Box<Future<Item = BroadcastResult, Error = ()> + Send>> = Vec::new();
+        for node in nodes {
+            let client = BitcoinClientImpl::from_node(self.http_client.clone(), &node)
+                .with_request_id(request_id.clone())
+                .with_trace(tracer.clone(), parent.clone());
+            let target = node.url;
+            results.push(Box::new(client.send_raw_transaction(params.clone()).then(move |r| {
+                Ok(match r {
+                    Ok(txid) => accepted(target, txid),
+                    Err(e) => {
+                        log_warn(&e);
+                        let rejection = match e.kind() {
+                            BitcoinClientErrorKind::Rpc(rpc_error) => BroadcastRejection {
+                                code: rpc_error.code,
+                                message: rpc_error.message,
+                            },
+                            kind => BroadcastRejection {
+                                code: MISC_ERROR_CODE,
+                                message: format!("{}", kind),
+                            },
+                        };
+                        rejected(target, rejection)
+                    }
+                })
+            })));
+        }
+        for client in &self.push_clients {
+            let target = client.url().to_string();
+            results.push(Box::new(client.push(hex.clone()).then(move |r| {
+                Ok(match r {
+                    Ok(txid) => accepted(target, txid),
+                    Err(e) => {
+                        log_warn(&e);
+                        let rejection = BroadcastRejection {
+                            code: MISC_ERROR_CODE,
+                            message: format!("{}", e.kind()),
+                        };
+                        rejected(target, rejection)
+                    }
+                })
+            })));
+        }
+        Box::new(future::join_all(results).map(|results| {
+            let txid = results.iter().filter(|r| r.accepted).filter_map(|r| r.txid.clone()).nth(0);
+            let rejection = if txid.is_none() { most_common_rejection(&results) } else { None };
+            BroadcastReport {
+                txid,
+                rejection,
+                results,
+                nodes: node_urls,
+            }
+        }))
+    }
+}
+
+fn accepted(target: String, txid: String) -> BroadcastResult {
+    BroadcastResult {
+        target,
+        accepted: true,
+        txid: Some(txid),
+        rejection: None,
+    }
+}
+
+fn rejected(target: String, rejection: BroadcastRejection) -> BroadcastResult {
+    BroadcastResult {
+        target,
+        accepted: false,
+        txid: None,
+        rejection: Some(rejection),
+    }
+}
+
+/// Picks rejection reported by most targets, preferring rpc rejections over transport errors.
+/// Ties are resolved in favor of the rejection seen first.
+fn most_common_rejection(results: &[BroadcastResult]) -> Option<BroadcastRejection> {
+    let mut counts: Vec<(&BroadcastRejection, usize)> = Vec::new();
+    for rejection in results.iter().filter_map(|r| r.rejection.as_ref()) {
+        match counts.iter().position(|(seen, _)| *seen == rejection) {
+            Some(i) => counts[i].1 += 1,
+            None => counts.push((rejection, 1)),
+        }
+    }
+    let mut best: Option<(&BroadcastRejection, usize)> = None;
+    for (rejection, count) in counts {
+        let key = (rejection.code != MISC_ERROR_CODE, count);
+        if best.map(|(b, c)| key > (b.code != MISC_ERROR_CODE, c)).unwrap_or(true) {
+            best = Some((rejection, count));
+        }
+    }
+    best.map(|(rejection, _)| rejection.clone())
+}
@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+
+use super::error::*;
+use prelude::*;
+
+lazy_static! {
+    /// User and password read from cookie files, by path
+    static ref COOKIES: Mutex<HashMap<String, (String, String)>> = Mutex::new(HashMap::new());
+}
+
+/// Returns user and password from bitcoind `.cookie` file. The file is read once and then
+/// again only with `reload`, since bitcoind writes a new cookie on every restart.
+pub fn credentials(path: &str, reload: bool) -> Result<(String, String), Error> {
+    if !reload {
+        if let Some(credentials) = COOKIES.lock().unwrap().get(path) {
+            return Ok(credentials.clone());
+        }
+    }
+    let content = fs::read_to_string(path).map_err(ectx!(try ErrorContext::Cookie, ErrorKind::Internal => path))?;
+    let mut parts = content.trim().splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(user), Some(password)) => {
+            let credentials = (user.to_string(), password.to_string());
+            COOKIES.lock().unwrap().insert(path.to_string(), credentials.clone());
+            Ok(credentials)
+        }
+        _ => Err(ectx!(err ErrorContext::Cookie, ErrorKind::Internal => path)),
+    }
+}
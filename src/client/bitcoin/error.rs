@@ -2,13 +2,15 @@ use failure::{Backtrace, Context, Fail};
 use std::fmt;
 use std::fmt::Display;
 
+use super::responses::RpcError;
+
 #[derive(Debug)]
 pub struct Error {
     inner: Context<ErrorKind>,
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+#[derive(Clone, Eq, PartialEq, Debug, Fail)]
 pub enum ErrorKind {
     #[fail(display = "http client error - bad request")]
     BadRequest,
@@ -28,6 +30,8 @@ pub enum ErrorKind {
     UnknownServerError,
     #[fail(display = "http client error - internal error")]
     Internal,
+    #[fail(display = "http client error - bitcoind returned rpc error")]
+    Rpc(RpcError),
 }
 
 #[allow(dead_code)]
@@ -45,6 +49,8 @@ pub enum ErrorContext {
     Topics,
     #[fail(display = "http client error - error converting rpc transaction into blockchain transaction")]
     BitcoinRpcConversion,
+    #[fail(display = "http client error - reading bitcoind cookie file")]
+    Cookie,
 }
 
 #[allow(dead_code)]
@@ -57,3 +63,12 @@ pub enum ErrorSource {
 }
 
 derive_error_impls!();
+
+impl ErrorKind {
+    pub fn is_rpc_code(&self, code: i64) -> bool {
+        match self {
+            ErrorKind::Rpc(rpc_error) => rpc_error.code == code,
+            _ => false,
+        }
+    }
+}
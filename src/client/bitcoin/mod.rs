@@ -1,31 +1,74 @@
+mod cookie;
 mod error;
 mod responses;
 
+pub use self::error::ErrorKind as BitcoinClientErrorKind;
+pub use self::responses::{Block, BlockchainInfo, MempoolEntry, RawTransaction, VerboseBlock};
+
 use std::sync::Arc;
 
+use futures::future::{self, Either};
 use hyper::{Body, Request, Response};
 
 use self::error::*;
 use self::responses::*;
+use super::http_client::error::{Error as HttpError, ErrorKind as HttpErrorKind};
 use super::http_client::HttpClient;
+use models::BitcoinNode;
 use prelude::*;
+use redact;
 use serde_json;
+use telemetry::{SpanContext, SpanKind, Tracer, TRACEPARENT_HEADER};
 use utils::read_body;
 
 /// Client for working with Bitcoin blockchain
 pub trait BitcoinClient: Send + Sync + 'static {
     /// Get last block hash
     fn get_last_block(&self) -> Box<Future<Item = u64, Error = Error> + Send>;
+    /// Get tip of the best chain
+    fn get_best_block(&self) -> Box<Future<Item = Block, Error = Error> + Send>;
+    /// Get height of the most-work fully-validated chain
+    fn get_block_count(&self) -> Box<Future<Item = u64, Error = Error> + Send>;
+    /// Get state of the chain, including network name
+    fn get_blockchain_info(&self) -> Box<Future<Item = BlockchainInfo, Error = Error> + Send>;
+    /// Get hash of the block at `height` in the best chain
+    fn get_block_hash(&self, height: u64) -> Box<Future<Item = String, Error = Error> + Send>;
+    /// Get block with list of txids
+    fn get_block(&self, hash: String) -> Box<Future<Item = Block, Error = Error> + Send>;
+    /// Get block with fully decoded transactions
+    fn get_verbose_block(&self, hash: String) -> Box<Future<Item = VerboseBlock, Error = Error> + Send>;
+    /// Get fee rate in BTC/kvB needed to confirm within `target` blocks
+    fn estimate_smart_fee(&self, target: u16) -> Box<Future<Item = Option<f64>, Error = Error> + Send>;
+    /// Get mempool entry of transaction, `None` if transaction is not in node's mempool
+    fn get_mempool_entry(&self, txid: String) -> Box<Future<Item = Option<MempoolEntry>, Error = Error> + Send>;
+    /// Get txids of all transactions in node's mempool
+    fn get_raw_mempool(&self) -> Box<Future<Item = Vec<String>, Error = Error> + Send>;
+    /// Get decoded transaction, needs `txindex` for transactions not in mempool
+    fn get_raw_transaction(&self, txid: String) -> Box<Future<Item = RawTransaction, Error = Error> + Send>;
+    /// Submit raw transaction to the node, returns txid. Params are passed to `sendrawtransaction` as is.
+    fn send_raw_transaction(&self, params: ::serde_json::Value) -> Box<Future<Item = String, Error = Error> + Send>;
     /// Get last block hash
     fn proxy_request(&self, params: &::serde_json::Value) -> Box<Future<Item = Response<Body>, Error = Error> + Send>;
 }
 
+/// Header with correlation id of a request
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Returned by bitcoind when transaction or block is not found
+pub const RPC_INVALID_ADDRESS_OR_KEY: i64 = -5;
+
 #[derive(Clone)]
 pub struct BitcoinClientImpl {
     http_client: Arc<HttpClient>,
     bitcoin_rpc_url: String,
     bitcoin_rpc_user: String,
     bitcoin_rpc_password: String,
+    /// Bitcoind `.cookie` file, used instead of user and password if set
+    bitcoin_rpc_cookie_file: Option<String>,
+    /// Correlation id of the api request this client serves, sent as `X-Request-Id`
+    request_id: Option<String>,
+    /// Tracer and parent span of upstream call spans, propagated as `traceparent`
+    trace: Option<(Tracer, SpanContext)>,
 }
 
 impl BitcoinClientImpl {
@@ -35,26 +78,101 @@ impl BitcoinClientImpl {
             bitcoin_rpc_url,
             bitcoin_rpc_user,
             bitcoin_rpc_password,
+            bitcoin_rpc_cookie_file: None,
+            request_id: None,
+            trace: None,
+        }
+    }
+
+    pub fn from_node(http_client: Arc<HttpClient>, node: &BitcoinNode) -> Self {
+        Self {
+            bitcoin_rpc_cookie_file: node.cookie_file.clone(),
+            ..Self::new(http_client, node.url.clone(), node.user.clone(), node.password.clone())
+        }
+    }
+
+    pub fn with_request_id(self, request_id: String) -> Self {
+        Self {
+            request_id: Some(request_id),
+            ..self
+        }
+    }
+
+    pub fn with_trace(self, tracer: Tracer, parent: SpanContext) -> Self {
+        Self {
+            trace: Some((tracer, parent)),
+            ..self
         }
     }
 
+    /// Sends request, reading cookie file again and retrying once if bitcoind rejects the cookie
     fn get_rpc_response(&self, params: &::serde_json::Value) -> Box<Future<Item = Response<Body>, Error = Error> + Send> {
+        let self_clone = self.clone();
+        let params_clone = params.clone();
+        Box::new(self.send_rpc_request(params, false).or_else(move |e| {
+            if e.kind() == ErrorKind::Unauthorized && self_clone.bitcoin_rpc_cookie_file.is_some() {
+                info!("Bitcoind at {} rejected cookie, reading it again", self_clone.bitcoin_rpc_url);
+                Either::A(self_clone.send_rpc_request(&params_clone, true))
+            } else {
+                Either::B(future::err(e))
+            }
+        }))
+    }
+
+    fn send_rpc_request(
+        &self,
+        params: &::serde_json::Value,
+        reload_cookie: bool,
+    ) -> Box<Future<Item = Response<Body>, Error = Error> + Send> {
         let http_client = self.http_client.clone();
-        let basic = ::base64::encode(&format!("{}:{}", self.bitcoin_rpc_user, self.bitcoin_rpc_password));
+        let (user, password) = match self.bitcoin_rpc_cookie_file {
+            Some(ref path) => match cookie::credentials(path, reload_cookie) {
+                Ok(credentials) => credentials,
+                Err(e) => return Box::new(future::err(e)),
+            },
+            None => (self.bitcoin_rpc_user.clone(), self.bitcoin_rpc_password.clone()),
+        };
+        let basic = ::base64::encode(&format!("{}:{}", user, password));
         let basic = format!("Basic {}", basic);
+        let span = self.trace.as_ref().map(|(tracer, parent)| {
+            let method = params["method"].as_str().unwrap_or("batch");
+            let mut span = tracer.start_span(&format!("bitcoind {}", method), SpanKind::Client, Some(parent));
+            span.set_attribute("rpc.system", "jsonrpc");
+            span.set_attribute("rpc.method", method);
+            span.set_attribute("server.url", self.bitcoin_rpc_url.as_str());
+            span
+        });
+        let traceparent = span.as_ref().map(|span| span.context().traceparent());
         Box::new(
             serde_json::to_string(params)
-                .map_err(ectx!(ErrorContext::Json, ErrorKind::Internal => params))
+                .map_err(ectx!(ErrorContext::Json, ErrorKind::Internal => redact::rpc_request(params)))
                 .and_then(|body| {
-                    Request::builder()
+                    let mut builder = Request::builder();
+                    builder
                         .method("POST")
                         .header("Authorization", basic)
-                        .uri(self.bitcoin_rpc_url.clone())
+                        .uri(self.bitcoin_rpc_url.clone());
+                    if let Some(ref request_id) = self.request_id {
+                        builder.header(REQUEST_ID_HEADER, request_id.as_str());
+                    }
+                    if let Some(traceparent) = traceparent {
+                        builder.header(TRACEPARENT_HEADER, traceparent);
+                    }
+                    builder
                         .body(Body::from(body.clone()))
-                        .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal => body))
+                        .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal => redact::request_body(body.as_bytes())))
                 })
                 .into_future()
-                .and_then(move |request| http_client.request(request).map_err(ectx!(ErrorKind::Internal))),
+                .and_then(move |request| http_client.request(request).map_err(rpc_error))
+                .then(move |r| {
+                    if let Some(mut span) = span {
+                        if let Err(ref e) = r {
+                            span.set_error(e);
+                        }
+                        span.end();
+                    }
+                    r
+                }),
         )
     }
 
@@ -62,7 +180,7 @@ impl BitcoinClientImpl {
     where
         for<'a> T: Send + 'static + ::serde::Deserialize<'a>,
     {
-        let params_clone = params.clone();
+        let params_clone = redact::rpc_request(params);
         self.get_rpc_response(params)
             .and_then(|resp| read_body(resp.into_body()).map_err(ectx!(ErrorKind::Internal => params_clone)))
             .and_then(|bytes| {
@@ -95,14 +213,114 @@ impl BitcoinClientImpl {
 
 impl BitcoinClient for BitcoinClientImpl {
     fn get_last_block(&self) -> Box<Future<Item = u64, Error = Error> + Send> {
+        Box::new(self.get_best_block().map(move |block| block.height))
+    }
+    fn get_best_block(&self) -> Box<Future<Item = Block, Error = Error> + Send> {
         let self_clone = self.clone();
-        Box::new(
-            self.get_best_block_hash()
-                .and_then(move |hash| self_clone.get_block_by_hash(hash))
-                .map(move |block| block.height),
-        )
+        Box::new(self.get_best_block_hash().and_then(move |hash| self_clone.get_block_by_hash(hash)))
+    }
+    fn get_block_count(&self) -> Box<Future<Item = u64, Error = Error> + Send> {
+        let params = json!({
+            "jsonrpc": "2",
+            "id": "1",
+            "method": "getblockcount",
+            "params": []
+        });
+        Box::new(self.get_response::<RpcBlockCountResponse>(&params).map(|r| r.result))
+    }
+    fn get_blockchain_info(&self) -> Box<Future<Item = BlockchainInfo, Error = Error> + Send> {
+        let params = json!({
+            "jsonrpc": "2",
+            "id": "1",
+            "method": "getblockchaininfo",
+            "params": []
+        });
+        Box::new(self.get_response::<RpcBlockchainInfoResponse>(&params).map(|r| r.result))
+    }
+    fn get_block_hash(&self, height: u64) -> Box<Future<Item = String, Error = Error> + Send> {
+        let params = json!({
+            "jsonrpc": "2",
+            "id": "1",
+            "method": "getblockhash",
+            "params": [height]
+        });
+        Box::new(self.get_response::<RpcBlockHashResponse>(&params).map(|r| r.result))
+    }
+    fn get_block(&self, hash: String) -> Box<Future<Item = Block, Error = Error> + Send> {
+        Box::new(self.get_block_by_hash(hash))
+    }
+    fn get_verbose_block(&self, hash: String) -> Box<Future<Item = VerboseBlock, Error = Error> + Send> {
+        let params = json!({
+            "jsonrpc": "2",
+            "id": "1",
+            "method": "getblock",
+            "params": [hash, 2]
+        });
+        Box::new(self.get_response::<RpcVerboseBlockResponse>(&params).map(|r| r.result))
+    }
+    fn estimate_smart_fee(&self, target: u16) -> Box<Future<Item = Option<f64>, Error = Error> + Send> {
+        let params = json!({
+            "jsonrpc": "2",
+            "id": "1",
+            "method": "estimatesmartfee",
+            "params": [target]
+        });
+        Box::new(self.get_response::<RpcEstimateSmartFeeResponse>(&params).map(|r| r.result.feerate))
+    }
+    fn get_mempool_entry(&self, txid: String) -> Box<Future<Item = Option<MempoolEntry>, Error = Error> + Send> {
+        let params = json!({
+            "jsonrpc": "2",
+            "id": "1",
+            "method": "getmempoolentry",
+            "params": [txid]
+        });
+        Box::new(self.get_response::<RpcMempoolEntryResponse>(&params).then(|r| match r {
+            Ok(r) => Ok(Some(r.result)),
+            Err(ref e) if e.kind().is_rpc_code(RPC_INVALID_ADDRESS_OR_KEY) => Ok(None),
+            Err(e) => Err(e),
+        }))
+    }
+    fn get_raw_mempool(&self) -> Box<Future<Item = Vec<String>, Error = Error> + Send> {
+        let params = json!({
+            "jsonrpc": "2",
+            "id": "1",
+            "method": "getrawmempool",
+            "params": []
+        });
+        Box::new(self.get_response::<RpcRawMempoolResponse>(&params).map(|r| r.result))
+    }
+    fn get_raw_transaction(&self, txid: String) -> Box<Future<Item = RawTransaction, Error = Error> + Send> {
+        let params = json!({
+            "jsonrpc": "2",
+            "id": "1",
+            "method": "getrawtransaction",
+            "params": [txid, true]
+        });
+        Box::new(self.get_response::<RpcRawTransactionResponse>(&params).map(|r| r.result))
+    }
+    fn send_raw_transaction(&self, params: ::serde_json::Value) -> Box<Future<Item = String, Error = Error> + Send> {
+        let params = json!({
+            "jsonrpc": "2",
+            "id": "1",
+            "method": "sendrawtransaction",
+            "params": params
+        });
+        Box::new(self.get_response::<RpcSendRawTransactionResponse>(&params).map(|r| r.result))
     }
     fn proxy_request(&self, body: &::serde_json::Value) -> Box<Future<Item = Response<Body>, Error = Error> + Send> {
         Box::new(self.get_rpc_response(body))
     }
 }
+
+/// Extracts json rpc error that bitcoind sends along with 500 status
+fn rpc_error(e: HttpError) -> Error {
+    let rpc_error = match e.kind() {
+        HttpErrorKind::InternalServerError(body) => serde_json::from_str::<RpcErrorResponse>(&body).ok().map(|r| r.error),
+        HttpErrorKind::Unauthorized => return ectx!(err e, ErrorKind::Unauthorized),
+        _ => None,
+    };
+    match rpc_error {
+        Some(rpc_error) => ectx!(err e, ErrorKind::Rpc(rpc_error)),
+        None => ectx!(err e, ErrorKind::Internal),
+    }
+}
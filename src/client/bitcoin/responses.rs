@@ -9,10 +9,129 @@ pub struct Block {
     pub previousblockhash: String,
     pub tx: Vec<String>,
     pub height: u64,
+    /// Block timestamp, unix time
+    pub time: u64,
     pub confirmations: usize,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcMempoolEntryResponse {
+    pub result: MempoolEntry,
+}
+
+/// Only presence of the entry is used, its fields are ignored
+#[derive(Debug, Clone, Deserialize)]
+pub struct MempoolEntry {}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct RpcBestBlockResponse {
     pub result: String,
 }
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcBlockCountResponse {
+    pub result: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcBlockchainInfoResponse {
+    pub result: BlockchainInfo,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockchainInfo {
+    /// Network name: `main`, `test`, `signet` or `regtest`
+    pub chain: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcBlockHashResponse {
+    pub result: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcEstimateSmartFeeResponse {
+    pub result: EstimateSmartFee,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EstimateSmartFee {
+    /// Fee rate in BTC/kvB, missing if node doesn't have enough data
+    pub feerate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcVerboseBlockResponse {
+    pub result: VerboseBlock,
+}
+
+/// Block as returned by `getblock` with verbosity 2
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerboseBlock {
+    pub hash: String,
+    pub previousblockhash: Option<String>,
+    pub height: u64,
+    pub tx: Vec<RawTransaction>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcRawMempoolResponse {
+    pub result: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcRawTransactionResponse {
+    pub result: RawTransaction,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawTransaction {
+    pub txid: String,
+    pub vin: Vec<RawTransactionInput>,
+    pub vout: Vec<RawTransactionOutput>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawTransactionInput {
+    pub txid: Option<String>,
+    pub vout: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawTransactionOutput {
+    pub value: f64,
+    pub n: u32,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pub_key: ScriptPubKey,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptPubKey {
+    /// Set by bitcoind >= 22
+    pub address: Option<String>,
+    /// Set by older bitcoind versions
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+impl ScriptPubKey {
+    pub fn address(&self) -> Option<String> {
+        self.address.clone().or_else(|| self.addresses.get(0).cloned())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcSendRawTransactionResponse {
+    pub result: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcErrorResponse {
+    pub error: RpcError,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
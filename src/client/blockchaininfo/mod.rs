@@ -10,7 +10,6 @@ use serde_json;
 
 pub use self::error::*;
 use super::HttpClient;
-use config::Config;
 use utils::read_body;
 
 pub trait BlockchainInfoClient: Send + Sync + 'static {
@@ -21,13 +20,16 @@ pub trait BlockchainInfoClient: Send + Sync + 'static {
 pub struct BlockchainInfoClientImpl {
     cli: Arc<HttpClient>,
     url: String,
+    /// Json pointer to height in response, response is a plain number if `None`
+    pointer: Option<String>,
 }
 
 impl BlockchainInfoClientImpl {
-    pub fn new<C: HttpClient>(config: &Config, cli: C) -> Self {
+    pub fn new<C: HttpClient>(url: String, pointer: Option<String>, cli: C) -> Self {
         Self {
             cli: Arc::new(cli),
-            url: config.healthcheck.url.clone(),
+            url,
+            pointer,
         }
     }
 
@@ -37,6 +39,7 @@ impl BlockchainInfoClientImpl {
         let query1 = query.clone();
         let query2 = query.clone();
         let cli = self.cli.clone();
+        let pointer = self.pointer.clone();
         let mut builder = Request::builder();
         builder.uri(url).method(Method::GET);
         builder.header("user-agent", "Mozilla/5.0 (X11; Ubuntu; Linu…) Gecko/20100101 Firefox/63.0");
@@ -50,7 +53,17 @@ impl BlockchainInfoClientImpl {
                 let bytes_clone = bytes.clone();
                 String::from_utf8(bytes).map_err(ectx!(ErrorSource::Utf8, ErrorKind::Internal => bytes_clone))
             })
-            .and_then(|string| serde_json::from_str::<u64>(&string).map_err(ectx!(ErrorSource::Json, ErrorKind::Internal => string)))
+            .and_then(move |string| match pointer {
+                Some(pointer) => serde_json::from_str::<serde_json::Value>(&string)
+                    .map_err(ectx!(ErrorSource::Json, ErrorKind::Internal => string))
+                    .and_then(|value| {
+                        value
+                            .pointer(&pointer)
+                            .and_then(|height| height.as_u64())
+                            .ok_or_else(|| ectx!(err ErrorSource::Json, ErrorKind::Internal => value, pointer))
+                    }),
+                None => serde_json::from_str::<u64>(&string).map_err(ectx!(ErrorSource::Json, ErrorKind::Internal => string)),
+            })
     }
 }
 
@@ -59,12 +72,3 @@ impl BlockchainInfoClient for BlockchainInfoClientImpl {
         Box::new(self.exec_query())
     }
 }
-
-#[derive(Default)]
-pub struct BlockchainInfoClientMock;
-
-impl BlockchainInfoClient for BlockchainInfoClientMock {
-    fn get_block_count(&self) -> Box<Future<Item = u64, Error = Error> + Send> {
-        Box::new(Ok(55563).into_future())
-    }
-}
@@ -0,0 +1,241 @@
+//! Connector choosing TLS settings and SOCKS5 proxy by upstream, so that nodes behind TLS terminating proxies
+//! can use private CAs, client certificates, SNI override and public key pinning, and nodes on onion services
+//! can be reached through Tor.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+use base64;
+use failure::Fail;
+use futures::future;
+use futures::prelude::*;
+use hyper::client::connect::{Connect, Connected, Destination, HttpConnector};
+use hyper::Uri;
+use hyper_tls::MaybeHttpsStream;
+use native_tls::{self, Certificate, HandshakeError, Identity, TlsConnector};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpStream;
+
+use super::error::*;
+use super::socks5;
+use config::{Config, NodeTls, Socks5Proxy};
+use redact;
+
+type Transport = MaybeHttpsStream<TcpStream>;
+
+/// TLS settings of a node
+#[derive(Clone)]
+struct UpstreamTls {
+    connector: TlsConnector,
+    server_name: Option<String>,
+    pin_sha256: Option<String>,
+}
+
+/// Connection settings of a node
+#[derive(Clone)]
+struct Upstream {
+    tls: Option<UpstreamTls>,
+    proxy: Option<Socks5Proxy>,
+}
+
+#[derive(Clone)]
+pub struct UpstreamConnector {
+    http: HttpConnector,
+    tls: TlsConnector,
+    /// Settings of nodes, by `host:port`
+    upstreams: Arc<HashMap<String, Upstream>>,
+    /// Proxy for everything but nodes, e.g. reference height and alerts
+    proxy: Option<Socks5Proxy>,
+    https_only: bool,
+}
+
+impl UpstreamConnector {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let mut http = HttpConnector::new(config.client.dns_threads);
+        http.enforce_http(false);
+        let tls = TlsConnector::new().map_err(ectx!(try ErrorSource::Tls, ErrorKind::Internal))?;
+        let nodes = config
+            .nodes
+            .iter()
+            .chain(config.networks.iter().flat_map(|network| network.nodes.iter()));
+        let mut upstreams = HashMap::new();
+        for node in nodes {
+            let upstream = Upstream {
+                tls: match node.tls {
+                    Some(ref node_tls) => Some(upstream_tls(node_tls)?),
+                    None => None,
+                },
+                proxy: node.proxy.clone(),
+            };
+            upstreams.insert(authority(&node.bitcoin_rpc_url)?, upstream);
+        }
+        Ok(Self {
+            http,
+            tls,
+            upstreams: Arc::new(upstreams),
+            proxy: config.client.proxy.clone(),
+            https_only: config.client.https_only,
+        })
+    }
+}
+
+impl Connect for UpstreamConnector {
+    type Transport = Transport;
+    type Error = io::Error;
+    type Future = Box<Future<Item = (Transport, Connected), Error = io::Error> + Send>;
+
+    fn connect(&self, dst: Destination) -> Self::Future {
+        let is_https = dst.scheme() == "https";
+        if !is_https && self.https_only {
+            let message = format!("Plain http to {} is not allowed with https_only", dst.host());
+            return Box::new(future::err(io::Error::new(io::ErrorKind::Other, message)));
+        }
+        let host = dst.host().to_string();
+        let port = dst.port().unwrap_or_else(|| default_port(is_https));
+        let upstream = format!("{}:{}", host, port);
+        let settings = self.upstreams.get(&upstream).cloned();
+        // nodes are reached directly unless they have own proxy, everything else goes through the default one
+        let proxy = match settings {
+            Some(ref settings) => settings.proxy.clone(),
+            None => self.proxy.clone(),
+        };
+        let connecting: Box<Future<Item = (TcpStream, Connected), Error = io::Error> + Send> = match proxy {
+            Some(proxy) => Box::new(socks5::connect(proxy, host.clone(), port).map(|tcp| (tcp, Connected::new()))),
+            None => Box::new(self.http.connect(dst)),
+        };
+        if !is_https {
+            return Box::new(connecting.map(|(tcp, connected)| (MaybeHttpsStream::Http(tcp), connected)));
+        }
+        let (tls, server_name, pin_sha256) = match settings.and_then(|settings| settings.tls) {
+            Some(settings) => (settings.connector, settings.server_name.unwrap_or(host), settings.pin_sha256),
+            None => (self.tls.clone(), host, None),
+        };
+        Box::new(connecting.and_then(move |(tcp, connected)| {
+            Handshaking {
+                inner: Some(tls.connect(&server_name, tcp)),
+            }
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .and_then(move |stream| {
+                if let Some(pin) = pin_sha256 {
+                    verify_pin(&stream, &pin, &upstream)?;
+                }
+                Ok((MaybeHttpsStream::from(stream), connected))
+            })
+        }))
+    }
+}
+
+struct Handshaking<T> {
+    inner: Option<Result<native_tls::TlsStream<T>, HandshakeError<T>>>,
+}
+
+impl<T: io::Read + io::Write> Future for Handshaking<T> {
+    type Item = native_tls::TlsStream<T>;
+    type Error = native_tls::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.inner.take().expect("polled after ready") {
+            Ok(stream) => Ok(Async::Ready(stream)),
+            Err(HandshakeError::WouldBlock(mid)) => match mid.handshake() {
+                Ok(stream) => Ok(Async::Ready(stream)),
+                Err(HandshakeError::Failure(e)) => Err(e),
+                Err(HandshakeError::WouldBlock(mid)) => {
+                    self.inner = Some(Err(HandshakeError::WouldBlock(mid)));
+                    Ok(Async::NotReady)
+                }
+            },
+            Err(HandshakeError::Failure(e)) => Err(e),
+        }
+    }
+}
+
+fn upstream_tls(config: &NodeTls) -> Result<UpstreamTls, Error> {
+    let mut builder = TlsConnector::builder();
+    if let Some(ref path) = config.ca_path {
+        let cert = Certificate::from_pem(&read(path)?).map_err(ectx!(try ErrorSource::Tls, ErrorKind::Internal => path))?;
+        builder.add_root_certificate(cert);
+    }
+    if let (Some(ref cert_path), Some(ref key_path)) = (&config.client_cert_path, &config.client_key_path) {
+        let identity = Identity::from_pkcs8(&read(cert_path)?, &read(key_path)?)
+            .map_err(ectx!(try ErrorSource::Tls, ErrorKind::Internal => cert_path, key_path))?;
+        builder.identity(identity);
+    }
+    let connector = builder.build().map_err(ectx!(try ErrorSource::Tls, ErrorKind::Internal))?;
+    Ok(UpstreamTls {
+        connector,
+        server_name: config.server_name.clone(),
+        pin_sha256: config.pin_sha256.clone(),
+    })
+}
+
+/// Checks base64 SHA-256 of server's SubjectPublicKeyInfo, the same format as `pin-sha256` of HPKP
+fn verify_pin<S: io::Read + io::Write>(stream: &native_tls::TlsStream<S>, pin: &str, upstream: &str) -> io::Result<()> {
+    let cert = stream
+        .peer_certificate()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+        .and_then(|cert| cert.to_der().ok());
+    let actual = cert
+        .as_ref()
+        .and_then(|der| subject_public_key_info(der))
+        .map(|spki| base64::encode(&Sha256::digest(spki)));
+    if actual.as_ref().map(|actual| actual == pin).unwrap_or(false) {
+        Ok(())
+    } else {
+        let message = format!("Public key of {} doesn't match pinned one, got {:?}", upstream, actual);
+        Err(io::Error::new(io::ErrorKind::Other, message))
+    }
+}
+
+/// Returns SubjectPublicKeyInfo of DER encoded X.509 certificate
+fn subject_public_key_info(cert: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _, _) = der_element(cert)?;
+    let (_, tbs_certificate, _, _) = der_element(certificate)?;
+    let (tag, _, _, after_version) = der_element(tbs_certificate)?;
+    // version is optional, tagged with [0]
+    let mut rest = if tag == 0xa0 { after_version } else { tbs_certificate };
+    // serial number, signature algorithm, issuer, validity and subject
+    for _ in 0..5 {
+        rest = der_element(rest)?.3;
+    }
+    der_element(rest).map(|(_, _, element, _)| element)
+}
+
+/// Splits DER element off the start of `data`, returns its tag, content, the whole element and the rest of data
+fn der_element(data: &[u8]) -> Option<(u8, &[u8], &[u8], &[u8])> {
+    let tag = *data.get(0)?;
+    let first = *data.get(1)? as usize;
+    let (header, len) = if first < 0x80 {
+        (2, first)
+    } else {
+        let octets = first & 0x7f;
+        if octets == 0 || octets > 4 {
+            return None;
+        }
+        let len = data.get(2..2 + octets)?.iter().fold(0, |len, byte| (len << 8) | *byte as usize);
+        (2 + octets, len)
+    };
+    let end = header.checked_add(len)?;
+    Some((tag, data.get(header..end)?, &data[..end], &data[end..]))
+}
+
+fn authority(url: &str) -> Result<String, Error> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(ectx!(try ErrorSource::Uri, ErrorKind::Internal => redact::url(url)))?;
+    let port = uri.port_u16().unwrap_or_else(|| default_port(uri.scheme_str() == Some("https")));
+    Ok(format!("{}:{}", uri.host().unwrap_or_default(), port))
+}
+
+fn default_port(is_https: bool) -> u16 {
+    if is_https {
+        443
+    } else {
+        80
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(ectx!(ErrorSource::Io, ErrorKind::Internal => path))
+}
@@ -31,6 +31,8 @@ pub enum ErrorKind {
     Internal,
     #[fail(display = "http client error - bad request")]
     Validation(String),
+    #[fail(display = "http client error - internal server error")]
+    InternalServerError(String),
 }
 
 #[allow(dead_code)]
@@ -40,5 +42,11 @@ pub enum ErrorSource {
     Hyper,
     #[fail(display = "http client source - server returned response with error")]
     Server,
+    #[fail(display = "http client source - error inside of TLS library")]
+    Tls,
+    #[fail(display = "http client source - io error")]
+    Io,
+    #[fail(display = "http client source - error parsing uri")]
+    Uri,
 }
 derive_error_impls!();
@@ -1,15 +1,18 @@
+mod connector;
 pub mod error;
+mod socks5;
 
 use config::Config;
 use failure::Fail;
 use futures::future::{self, Either};
 use futures::prelude::*;
 use hyper;
-use hyper::{client::HttpConnector, Body, Request, Response};
-use hyper_tls::HttpsConnector;
+use hyper::{Body, Request, Response};
 use log::{self, Level};
 
+use self::connector::UpstreamConnector;
 use self::error::*;
+use redact;
 use utils::read_body;
 
 pub trait HttpClient: Send + Sync + 'static {
@@ -18,13 +21,12 @@ pub trait HttpClient: Send + Sync + 'static {
 
 #[derive(Clone)]
 pub struct HttpClientImpl {
-    cli: hyper::Client<HttpsConnector<HttpConnector>>,
+    cli: hyper::Client<UpstreamConnector>,
 }
 
 impl HttpClientImpl {
     pub fn new(config: &Config) -> Self {
-        let connector = HttpsConnector::new(config.client.dns_threads).unwrap();
-        //connector.https_only(true);
+        let connector = UpstreamConnector::new(config).unwrap_or_else(|e| panic!("Error creating http client: {}", e));
         let cli = hyper::Client::builder().build(connector);
         Self { cli }
     }
@@ -41,27 +43,29 @@ impl HttpClient for HttpClientImpl {
                     .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal))
                     .and_then(move |body| {
                         debug!(
-                            "HttpClient, sent request {} {}, headers: {:#?}, body: {:?}",
+                            "HttpClient, sent request {} {}, headers: {:#?}, body: {}",
                             parts.method,
-                            parts.uri,
-                            parts.headers,
-                            String::from_utf8(body.clone()).ok()
+                            redact::url(&parts.uri.to_string()),
+                            redact::headers(&parts.headers),
+                            redact::request_body(&body)
                         );
-                        let req = Request::from_parts(parts, body.into());
-                        cli.request(req).map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal))
+                        let req = Request::from_parts(parts, body.clone().into());
+                        cli.request(req)
+                            .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal))
+                            .map(|resp| (resp, body))
                     })
-                    .and_then(|resp| {
+                    .and_then(|(resp, request_body)| {
                         let (parts, body) = resp.into_parts();
                         read_body(body)
                             .map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal))
-                            .map(|body| (parts, body))
+                            .map(|body| (parts, body, request_body))
                     })
-                    .map(|(parts, body)| {
+                    .map(|(parts, body, request_body)| {
                         debug!(
-                            "HttpClient, recieved response with status {} headers: {:#?} and body: {:?}",
+                            "HttpClient, recieved response with status {} headers: {:#?} and body: {}",
                             parts.status.as_u16(),
-                            parts.headers,
-                            String::from_utf8(body.clone()).ok()
+                            redact::headers(&parts.headers),
+                            redact::response_body(&request_body, &body)
                         );
                         Response::from_parts(parts, body.into())
                     }),
@@ -76,13 +80,18 @@ impl HttpClient for HttpClientImpl {
                     400 => Either::A(future::err(ectx!(err ErrorSource::Server, ErrorKind::BadRequest))),
                     401 => Either::A(future::err(ectx!(err ErrorSource::Server, ErrorKind::Unauthorized))),
                     404 => Either::A(future::err(ectx!(err ErrorSource::Server, ErrorKind::NotFound))),
-                    422 => Either::B(read_body(resp.into_body()).then(|body| match body {
+                    status @ 422 | status @ 500 => Either::B(read_body(resp.into_body()).then(move |body| match body {
                         Ok(b) => {
-                            future::err(ectx!(err ErrorSource::Server, ErrorKind::Validation(String::from_utf8(b).unwrap_or_default())))
+                            let body = String::from_utf8(b).unwrap_or_default();
+                            let kind = if status == 422 {
+                                ErrorKind::Validation(body)
+                            } else {
+                                ErrorKind::InternalServerError(body)
+                            };
+                            future::err(ectx!(err ErrorSource::Server, kind))
                         }
                         Err(_) => future::err(ectx!(err ErrorSource::Server, ErrorKind::UnknownServerError)),
                     })),
-                    500 => Either::A(future::err(ectx!(err ErrorSource::Server, ErrorKind::Internal))),
                     502 => Either::A(future::err(ectx!(err ErrorSource::Server, ErrorKind::BadGateway))),
                     504 => Either::A(future::err(ectx!(err ErrorSource::Server, ErrorKind::GatewayTimeout))),
                     _ => Either::A(future::err(ectx!(err ErrorSource::Server, ErrorKind::UnknownServerError))),
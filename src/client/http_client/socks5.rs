@@ -0,0 +1,99 @@
+//! Minimal SOCKS5 client (RFC 1928 and 1929). Host names are resolved by the proxy, so `.onion` addresses work with Tor.
+
+use std::io;
+use std::net::SocketAddr;
+
+use futures::future::{self, Either};
+use futures::prelude::*;
+use tokio::io::{read_exact, write_all};
+use tokio::net::TcpStream;
+
+use config::Socks5Proxy;
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const USERNAME_PASSWORD: u8 = 2;
+const CONNECT: u8 = 1;
+const DOMAIN_NAME: u8 = 3;
+
+/// Connects to `host:port` through the proxy
+pub fn connect(proxy: Socks5Proxy, host: String, port: u16) -> impl Future<Item = TcpStream, Error = io::Error> + Send {
+    let credentials = match (proxy.username.clone(), proxy.password.clone()) {
+        (Some(username), Some(password)) => Some((username, password)),
+        _ => None,
+    };
+    let method = if credentials.is_some() { USERNAME_PASSWORD } else { NO_AUTH };
+    future::result(
+        proxy
+            .address
+            .parse::<SocketAddr>()
+            .map_err(|e| other(format!("Invalid proxy address: {}", e))),
+    )
+    .and_then(|addr| TcpStream::connect(&addr))
+    .and_then(move |stream| write_all(stream, [VERSION, 1, method]))
+    .and_then(|(stream, _)| read_exact(stream, [0u8; 2]))
+    .and_then(move |(stream, reply)| {
+        if reply != [VERSION, method] {
+            return Either::A(future::err(other(format!("Proxy refused authentication method {}", method))));
+        }
+        match credentials {
+            Some((username, password)) => Either::B(Either::A(authenticate(stream, &username, &password))),
+            None => Either::B(Either::B(future::ok(stream))),
+        }
+    })
+    .and_then(move |stream| {
+        if host.len() > 255 {
+            return Either::A(future::err(other(format!("Host name {} is too long", host))));
+        }
+        let mut request = vec![VERSION, CONNECT, 0, DOMAIN_NAME, host.len() as u8];
+        request.extend_from_slice(host.as_bytes());
+        request.extend_from_slice(&[(port >> 8) as u8, port as u8]);
+        Either::B(write_all(stream, request))
+    })
+    .and_then(|(stream, _)| read_exact(stream, [0u8; 4]))
+    .and_then(|(stream, reply)| {
+        if reply[1] != 0 {
+            return Either::A(future::err(other(format!("Proxy failed to connect, reply code {}", reply[1]))));
+        }
+        // skip address the proxy bound to, its length depends on address type
+        let address_len = match reply[3] {
+            1 => Either::A(future::ok((stream, 4))),
+            4 => Either::A(future::ok((stream, 16))),
+            DOMAIN_NAME => Either::B(read_exact(stream, [0u8; 1]).map(|(stream, len)| (stream, len[0] as usize))),
+            atyp => return Either::A(future::err(other(format!("Unknown address type {}", atyp)))),
+        };
+        Either::B(
+            address_len
+                .and_then(|(stream, len)| read_exact(stream, vec![0u8; len + 2]))
+                .map(|(stream, _)| stream),
+        )
+    })
+}
+
+fn authenticate(stream: TcpStream, username: &str, password: &str) -> impl Future<Item = TcpStream, Error = io::Error> + Send {
+    // lengths are sent in one byte
+    if username.len() > 255 || password.len() > 255 {
+        return Either::A(future::err(other(
+            "Proxy username or password is longer than 255 bytes".to_string(),
+        )));
+    }
+    let mut request = vec![1, username.len() as u8];
+    request.extend_from_slice(username.as_bytes());
+    request.push(password.len() as u8);
+    request.extend_from_slice(password.as_bytes());
+    Either::B(
+        write_all(stream, request)
+            .and_then(|(stream, _)| read_exact(stream, [0u8; 2]))
+            .and_then(|(stream, reply)| {
+                if reply[1] == 0 {
+                    Ok(stream)
+                } else {
+                    Err(other("Proxy rejected username and password".to_string()))
+                }
+            }),
+    )
+}
+
+fn other(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message)
+}
@@ -0,0 +1,59 @@
+mod error;
+mod responses;
+
+use std::sync::Arc;
+
+use failure::Fail;
+use futures::prelude::*;
+use hyper::Method;
+use hyper::{Body, Request};
+use serde_json;
+
+pub use self::error::*;
+pub use self::responses::*;
+use super::HttpClient;
+use utils::read_body;
+
+pub trait MempoolClient: Send + Sync + 'static {
+    fn get_recommended_fees(&self) -> Box<Future<Item = MempoolFees, Error = Error> + Send>;
+}
+
+#[derive(Clone)]
+pub struct MempoolClientImpl {
+    cli: Arc<HttpClient>,
+    url: String,
+}
+
+impl MempoolClientImpl {
+    pub fn new(cli: Arc<HttpClient>, url: String) -> Self {
+        Self { cli, url }
+    }
+
+    fn exec_query(&self) -> impl Future<Item = MempoolFees, Error = Error> + Send {
+        let url = self.url.clone();
+        let query1 = url.clone();
+        let query2 = url.clone();
+        let cli = self.cli.clone();
+        let mut builder = Request::builder();
+        builder.uri(url).method(Method::GET);
+        builder
+            .body(Body::empty())
+            .map_err(ectx!(ErrorSource::Hyper, ErrorKind::MalformedInput))
+            .into_future()
+            .and_then(move |req| cli.request(req).map_err(ectx!(ErrorKind::Internal => query1)))
+            .and_then(move |resp| read_body(resp.into_body()).map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal => query2)))
+            .and_then(|bytes| {
+                let bytes_clone = bytes.clone();
+                String::from_utf8(bytes).map_err(ectx!(ErrorSource::Utf8, ErrorKind::Internal => bytes_clone))
+            })
+            .and_then(|string| {
+                serde_json::from_str::<MempoolFees>(&string).map_err(ectx!(ErrorSource::Json, ErrorKind::Internal => string))
+            })
+    }
+}
+
+impl MempoolClient for MempoolClientImpl {
+    fn get_recommended_fees(&self) -> Box<Future<Item = MempoolFees, Error = Error> + Send> {
+        Box::new(self.exec_query())
+    }
+}
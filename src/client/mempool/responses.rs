@@ -0,0 +1,21 @@
+/// Recommended fees in sat/vB, as returned by mempool.space compatible APIs
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MempoolFees {
+    pub fastest_fee: f64,
+    pub half_hour_fee: f64,
+    pub hour_fee: f64,
+    pub economy_fee: f64,
+}
+
+impl MempoolFees {
+    /// Maps confirmation target in blocks to the closest recommendation
+    pub fn for_target(&self, target: u16) -> f64 {
+        match target {
+            0..=1 => self.fastest_fee,
+            2..=3 => self.half_hour_fee,
+            4..=6 => self.hour_fee,
+            _ => self.economy_fee,
+        }
+    }
+}
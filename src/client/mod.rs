@@ -1,9 +1,15 @@
 pub mod bitcoin;
 pub mod blockchaininfo;
 pub mod http_client;
+pub mod mempool;
 pub mod opsgenie;
+pub mod push_tx;
+pub mod webhook;
 
 pub use self::bitcoin::*;
 pub use self::blockchaininfo::*;
 pub use self::http_client::*;
+pub use self::mempool::*;
 pub use self::opsgenie::*;
+pub use self::push_tx::*;
+pub use self::webhook::*;
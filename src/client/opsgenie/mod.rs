@@ -15,10 +15,14 @@ pub use self::error::*;
 use self::responses::*;
 use super::HttpClient;
 use config::Config;
+use models::*;
 use utils::read_body;
 
 pub trait OpsGenieClient: Send + Sync + 'static {
-    fn notify(&self, message: String) -> Box<Future<Item = (), Error = Error> + Send>;
+    /// Creates alert. OpsGenie de-duplicates open alerts with the same alias.
+    fn notify(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send>;
+    /// Closes open alert with the alias
+    fn close(&self, alias: String) -> Box<Future<Item = (), Error = Error> + Send>;
 }
 
 #[derive(Clone)]
@@ -41,8 +45,7 @@ impl OpsGenieClientImpl {
         }
     }
 
-    fn exec_query<T: for<'de> Deserialize<'de> + Send>(&self, body: String) -> impl Future<Item = T, Error = Error> + Send {
-        let url = self.url.clone();
+    fn exec_query<T: for<'de> Deserialize<'de> + Send>(&self, url: String, body: String) -> impl Future<Item = T, Error = Error> + Send {
         let query1 = url.clone();
         let query2 = url.clone();
         let query3 = url.clone();
@@ -66,28 +69,48 @@ impl OpsGenieClientImpl {
 }
 
 impl OpsGenieClient for OpsGenieClientImpl {
-    fn notify(&self, message: String) -> Box<Future<Item = (), Error = Error> + Send> {
+    fn notify(&self, alert: &Alert) -> Box<Future<Item = (), Error = Error> + Send> {
         if self.enabled {
             let client = self.clone();
+            let url = self.url.clone();
             let team = self.team.clone();
-            let payload = OpsGeniePayload::new(message, team);
+            let payload = OpsGeniePayload::new(alert.message.clone(), team, alert.alias(), alert.severity.into());
             Box::new(
                 serde_json::to_string(&payload)
                     .map_err(ectx!(ErrorSource::Json, ErrorKind::Internal => payload))
                     .into_future()
-                    .and_then(move |body| client.exec_query::<OpsGenieResponse>(body).map(move |_| ())),
+                    .and_then(move |body| client.exec_query::<OpsGenieResponse>(url, body).map(move |_| ())),
             )
         } else {
             Box::new(future::ok(()))
         }
     }
-}
-
-#[derive(Default)]
-pub struct OpsGenieClientMock;
 
-impl OpsGenieClient for OpsGenieClientMock {
-    fn notify(&self, _message: String) -> Box<Future<Item = (), Error = Error> + Send> {
-        Box::new(Ok(()).into_future())
+    fn close(&self, alias: String) -> Box<Future<Item = (), Error = Error> + Send> {
+        if self.enabled {
+            let client = self.clone();
+            let url = format!("{}/{}/close?identifierType=alias", self.url, encode_path_segment(&alias));
+            let payload = OpsGenieClosePayload::new("Condition cleared".to_string());
+            Box::new(
+                serde_json::to_string(&payload)
+                    .map_err(ectx!(ErrorSource::Json, ErrorKind::Internal => payload))
+                    .into_future()
+                    .and_then(move |body| client.exec_query::<OpsGenieResponse>(url, body).map(move |_| ())),
+            )
+        } else {
+            Box::new(future::ok(()))
+        }
     }
 }
+
+/// Percent-encodes everything but unreserved characters, aliases contain node urls with `/` and `:`
+fn encode_path_segment(segment: &str) -> String {
+    segment.bytes().fold(String::new(), |mut result, b| {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            result.push(b as char);
+        } else {
+            result.push_str(&format!("%{:02X}", b));
+        }
+        result
+    })
+}
@@ -1,3 +1,5 @@
+use models::*;
+
 #[derive(Debug, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct OpsGenieResponse {
@@ -10,10 +12,40 @@ pub struct OpsGenieResponse {
 #[serde(rename_all = "camelCase")]
 pub struct OpsGeniePayload {
     pub message: String,
+    pub alias: String,
+    pub priority: OpsGeniePriority,
     pub responders: Vec<OpsGenieUser>,
     pub visible_to: Vec<OpsGenieUser>,
 }
 
+#[derive(Debug, Serialize, Clone, Copy)]
+pub enum OpsGeniePriority {
+    P1,
+    P3,
+    P5,
+}
+
+impl From<AlertSeverity> for OpsGeniePriority {
+    fn from(severity: AlertSeverity) -> Self {
+        match severity {
+            AlertSeverity::Critical => OpsGeniePriority::P1,
+            AlertSeverity::Warning => OpsGeniePriority::P3,
+            AlertSeverity::Info => OpsGeniePriority::P5,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OpsGenieClosePayload {
+    pub note: String,
+}
+
+impl OpsGenieClosePayload {
+    pub fn new(note: String) -> Self {
+        Self { note }
+    }
+}
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct OpsGenieUser {
@@ -23,9 +55,11 @@ pub struct OpsGenieUser {
 }
 
 impl OpsGeniePayload {
-    pub fn new(message: String, team_name: String) -> Self {
+    pub fn new(message: String, team_name: String, alias: String, priority: OpsGeniePriority) -> Self {
         Self {
             message,
+            alias,
+            priority,
             responders: vec![OpsGenieUser::new(team_name.clone())],
             visible_to: vec![OpsGenieUser::new(team_name.clone())],
         }
@@ -0,0 +1,61 @@
+mod error;
+
+use std::sync::Arc;
+
+use failure::Fail;
+use futures::prelude::*;
+use hyper::Method;
+use hyper::{Body, Request};
+
+pub use self::error::*;
+use super::HttpClient;
+use utils::read_body;
+
+/// Client for external transaction push APIs that accept raw transaction hex as body
+/// and return txid as plain text (e.g. blockstream.info `/api/tx`)
+pub trait PushTxClient: Send + Sync + 'static {
+    fn push(&self, hex: String) -> Box<Future<Item = String, Error = Error> + Send>;
+}
+
+#[derive(Clone)]
+pub struct PushTxClientImpl {
+    cli: Arc<HttpClient>,
+    url: String,
+}
+
+impl PushTxClientImpl {
+    pub fn new(cli: Arc<HttpClient>, url: String) -> Self {
+        Self { cli, url }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn exec_query(&self, hex: String) -> impl Future<Item = String, Error = Error> + Send {
+        let url = self.url.clone();
+        let query1 = url.clone();
+        let query2 = url.clone();
+        let cli = self.cli.clone();
+        let mut builder = Request::builder();
+        builder.uri(url).method(Method::POST);
+        builder.header("Content-Type", "text/plain");
+        builder
+            .body(Body::from(hex))
+            .map_err(ectx!(ErrorSource::Hyper, ErrorKind::MalformedInput))
+            .into_future()
+            .and_then(move |req| cli.request(req).map_err(ectx!(ErrorKind::Internal => query1)))
+            .and_then(move |resp| read_body(resp.into_body()).map_err(ectx!(ErrorSource::Hyper, ErrorKind::Internal => query2)))
+            .and_then(|bytes| {
+                let bytes_clone = bytes.clone();
+                String::from_utf8(bytes).map_err(ectx!(ErrorSource::Utf8, ErrorKind::Internal => bytes_clone))
+            })
+            .map(|txid| txid.trim().to_string())
+    }
+}
+
+impl PushTxClient for PushTxClientImpl {
+    fn push(&self, hex: String) -> Box<Future<Item = String, Error = Error> + Send> {
+        Box::new(self.exec_query(hex))
+    }
+}
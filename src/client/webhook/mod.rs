@@ -0,0 +1,63 @@
+mod error;
+
+use std::sync::Arc;
+
+use failure::Fail;
+use futures::prelude::*;
+use hex;
+use hmac::{Hmac, Mac};
+use hyper::Method;
+use hyper::{Body, Request};
+use sha2::Sha256;
+
+pub use self::error::*;
+use super::HttpClient;
+use redact;
+
+/// Header with hex encoded HMAC-SHA256 of the request body, keyed with subscriber secret
+pub const SIGNATURE_HEADER: &str = "X-Signature-SHA256";
+
+/// Client delivering json payloads to webhook subscribers
+pub trait WebhookClient: Send + Sync + 'static {
+    /// POST `body` to `url`, signing it if `secret` is set
+    fn send(&self, url: String, secret: Option<String>, body: String) -> Box<Future<Item = (), Error = Error> + Send>;
+}
+
+#[derive(Clone)]
+pub struct WebhookClientImpl {
+    cli: Arc<HttpClient>,
+}
+
+impl WebhookClientImpl {
+    pub fn new(cli: Arc<HttpClient>) -> Self {
+        Self { cli }
+    }
+}
+
+impl WebhookClient for WebhookClientImpl {
+    fn send(&self, url: String, secret: Option<String>, body: String) -> Box<Future<Item = (), Error = Error> + Send> {
+        let cli = self.cli.clone();
+        let query = redact::url(&url);
+        let mut builder = Request::builder();
+        builder.uri(url).method(Method::POST);
+        builder.header("Content-Type", "application/json");
+        if let Some(secret) = secret {
+            builder.header(SIGNATURE_HEADER, sign(&secret, &body));
+        }
+        Box::new(
+            builder
+                .body(Body::from(body))
+                .map_err(ectx!(ErrorSource::Hyper, ErrorKind::MalformedInput))
+                .into_future()
+                .and_then(move |req| cli.request(req).map_err(ectx!(ErrorKind::Internal => query)))
+                .map(|_| ()),
+        )
+    }
+}
+
+fn sign(secret: &str, body: &str) -> String {
+    // hmac accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("Invalid HMAC key length");
+    mac.input(body.as_bytes());
+    hex::encode(mac.result().code())
+}
@@ -1,8 +1,10 @@
 use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
+use std::fs;
 
 use config_crate::{Config as RawConfig, ConfigError, Environment, File};
-use logger::{FileLogConfig, GrayLogConfig};
+use logger::{FileLogConfig, GrayLogConfig, LogLevels};
 use models::*;
 use sentry_integration::SentryConfig;
 
@@ -12,30 +14,350 @@ pub struct Config {
     pub client: Client,
     pub cpu_pool: CpuPool,
     pub nodes: Vec<Node>,
+    /// Expected chain of `nodes` as reported by `getblockchaininfo`, not verified if not set
+    pub chain: Option<String>,
+    /// Additional node pools, each served under its own path prefix or hosts
+    #[serde(default)]
+    pub networks: Vec<Network>,
     pub healthcheck: Healthcheck,
     pub opsgenie: OpsGenie,
+    pub alerts: Option<Alerts>,
+    pub admin: Option<Admin>,
+    pub audit: Option<Audit>,
     pub sentry: Option<SentryConfig>,
     pub graylog: Option<GrayLogConfig>,
     pub filelog: Option<FileLogConfig>,
+    /// Levels of log targets, overridden by `RUST_LOG` and changeable with admin api
+    pub logging: Option<LogLevels>,
+    pub indexer: Option<Indexer>,
+    #[serde(default)]
+    pub fees: Fees,
+    #[serde(default)]
+    pub broadcast: Broadcast,
+    pub tracking: Option<Tracking>,
+    pub notifications: Option<Notifications>,
+    pub webhooks: Option<Webhooks>,
+    pub watches: Option<Watches>,
+    pub telemetry: Option<Telemetry>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Healthcheck {
     pub timeout: u64,
     pub url: String,
+    /// Duration of the first quarantine, doubled on each next one, in seconds
     pub quarantine: i64,
+    /// Upper limit for quarantine duration, in seconds
+    pub max_quarantine: i64,
+    /// Node healthy for this long starts again from the first quarantine duration, in seconds
+    pub backoff_reset: i64,
+    pub lag: LagThresholds,
+    pub probation: Probation,
+    pub state: Option<NodeState>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NodeState {
+    /// Path to json file with health state of nodes
+    pub path: String,
+    /// Saved state older than this is ignored on startup, in seconds
+    pub max_age: i64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Probation {
+    /// Number of passed healthchecks after which node released from quarantine is fully trusted again
+    pub checks: u32,
+    /// Share of read-only proxied requests sent to nodes on probation, in percent. Wallet calls always go to the active node.
+    pub traffic_percent: u64,
+}
+
+/// Node lag is measured in blocks behind reference height and in seconds since node's last block
+#[derive(Debug, Deserialize, Clone)]
+pub struct LagThresholds {
+    pub warning_blocks: u64,
+    pub quarantine_blocks: u64,
+    pub warning_seconds: Option<i64>,
+    pub quarantine_seconds: Option<i64>,
+    /// Number of consecutive healthchecks over quarantine threshold before node is quarantined
+    pub grace_checks: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Indexer {
+    /// Path to index database directory
+    pub path: String,
+    /// Interval between polls for new blocks, in seconds
+    pub interval: u64,
+    /// Height of first block to index. Address balances and utxo miss funds received below it.
+    pub start_height: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Fees {
+    /// Confirmation targets in blocks
+    pub targets: Vec<u16>,
+    /// Lower bound for recommended fee rate, in sat/vB
+    pub min_fee_rate: u64,
+    /// Upper bound for recommended fee rate, in sat/vB
+    pub max_fee_rate: u64,
+    /// mempool.space compatible recommended fees endpoint
+    pub mempool_url: Option<String>,
+}
+
+impl Default for Fees {
+    fn default() -> Self {
+        Self {
+            targets: vec![1, 3, 6, 12, 24, 144],
+            min_fee_rate: 1,
+            max_fee_rate: 1000,
+            mempool_url: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Broadcast {
+    /// External APIs that accept raw transaction hex, e.g. `https://blockstream.info/api/tx`
+    pub push_urls: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Tracking {
+    /// Interval between checks of broadcasted transactions, in seconds
+    pub interval: u64,
+    /// Transactions with this many confirmations are no longer tracked
+    pub forget_after_confirmations: u64,
+    /// Unconfirmed transactions older than this are no longer tracked, in seconds
+    pub max_age: i64,
+    /// Rebroadcasts of a transaction to one node, each after twice longer delay than the previous one
+    #[serde(default = "default_max_rebroadcasts")]
+    pub max_rebroadcasts: u32,
+    /// Path to json file with tracked transactions, they are kept in memory only when missing
+    pub state_path: Option<String>,
+}
+
+fn default_max_rebroadcasts() -> u32 {
+    10
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Webhooks {
+    /// Interval between polls of the active node for new blocks, in seconds
+    pub interval: u64,
+    pub retry: WebhookRetry,
+    pub subscribers: Vec<WebhookSubscriber>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Watches {
+    /// Interval between scans of new blocks and mempool, in seconds
+    pub interval: u64,
+    /// Upper limit for confirmations requested by a watch
+    pub max_confirmations: u64,
+    /// Maximum number of new mempool transactions decoded per scan when addresses are watched
+    pub max_mempool_transactions: usize,
+    /// Number of mempool transactions decoded at the same time
+    #[serde(default = "default_mempool_concurrency")]
+    pub mempool_concurrency: usize,
+    /// Hosts that anyone may set as callback url, callbacks to other hosts require admin token
+    #[serde(default)]
+    pub callback_hosts: Vec<String>,
+    /// Watches are forgotten after this time even if not confirmed, in seconds
+    #[serde(default = "default_watch_ttl")]
+    pub ttl: i64,
+    pub retry: WebhookRetry,
+}
+
+fn default_mempool_concurrency() -> usize {
+    4
+}
+
+fn default_watch_ttl() -> i64 {
+    604800
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebhookRetry {
+    /// Number of delivery attempts before webhook is dropped
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on each next one, in seconds
+    pub backoff: u64,
+    /// Upper limit for delay between retries, in seconds
+    #[serde(default = "default_max_backoff")]
+    pub max_backoff: u64,
+}
+
+fn default_max_backoff() -> u64 {
+    3600
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebhookSubscriber {
+    pub url: String,
+    /// Key for HMAC-SHA256 signature of the payload, sent in `X-Signature-SHA256` header
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Notifications {
+    /// Host of websocket server
+    pub host: String,
+    /// Port of websocket server
+    pub port: String,
+    /// ZMQ topics to relay, e.g. `hashblock`, `hashtx`, `rawblock`, `rawtx`
+    pub topics: Vec<String>,
+    /// Maximum number of websocket subscribers connected at the same time
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    /// Notifications queued for a subscriber, it's disconnected when the queue is full
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+}
+
+fn default_max_connections() -> usize {
+    100
+}
+
+fn default_queue_size() -> usize {
+    1000
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Client {
     pub dns_threads: usize,
+    /// Refuse plain http connections to nodes and external services
+    #[serde(default)]
+    pub https_only: bool,
+    /// Proxy for external services, e.g. reference height and alerts. Nodes use only their own `proxy`.
+    pub proxy: Option<Socks5Proxy>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Socks5Proxy {
+    /// Proxy address as `ip:port`, e.g. `127.0.0.1:9050` for Tor
+    pub address: String,
+    /// Credentials for proxies that require them, Tor uses them to isolate streams
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl fmt::Debug for Socks5Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Socks5Proxy")
+            .field("address", &self.address)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "********"))
+            .finish()
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Node {
     pub bitcoin_rpc_url: String,
+    /// Not needed with `bitcoin_rpc_cookie_file`
+    #[serde(default)]
     pub bitcoin_rpc_user: String,
+    #[serde(default)]
     pub bitcoin_rpc_password: String,
+    /// File with password, e.g. a mounted secret for user from bitcoind `rpcauth`
+    pub bitcoin_rpc_password_file: Option<String>,
+    /// Environment variable with password
+    pub bitcoin_rpc_password_env: Option<String>,
+    /// Bitcoind `.cookie` file, re-read when bitcoind rejects the cookie after restart
+    pub bitcoin_rpc_cookie_file: Option<String>,
+    /// ZMQ endpoint bitcoind publishes notifications to, e.g. `tcp://127.0.0.1:28332`
+    pub zmq_url: Option<String>,
+    /// TLS options for https `bitcoin_rpc_url`
+    pub tls: Option<NodeTls>,
+    /// SOCKS5 proxy to reach the node through, needed for `.onion` nodes
+    pub proxy: Option<Socks5Proxy>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NodeTls {
+    /// PEM file with CA certificate trusted in addition to system ones
+    pub ca_path: Option<String>,
+    /// PEM file with client certificate, used together with `client_key_path`
+    pub client_cert_path: Option<String>,
+    /// PEM file with PKCS8 private key of client certificate
+    pub client_key_path: Option<String>,
+    /// Name sent in SNI and checked against node's certificate, host of `bitcoin_rpc_url` if not set
+    pub server_name: Option<String>,
+    /// Base64 SHA-256 of node's public key (SubjectPublicKeyInfo), connection is refused if it doesn't match
+    pub pin_sha256: Option<String>,
+}
+
+impl Node {
+    /// Password from `bitcoin_rpc_password_env` or `bitcoin_rpc_password_file` if set, `bitcoin_rpc_password` otherwise
+    pub fn password(&self) -> String {
+        if let Some(ref var) = self.bitcoin_rpc_password_env {
+            return env::var(var).unwrap_or_else(|e| panic!("Error reading bitcoin rpc password from {}: {}", var, e));
+        }
+        if let Some(ref path) = self.bitcoin_rpc_password_file {
+            return fs::read_to_string(path)
+                .map(|password| password.trim().to_string())
+                .unwrap_or_else(|e| panic!("Error reading bitcoin rpc password from {}: {}", path, e));
+        }
+        self.bitcoin_rpc_password.clone()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Network {
+    /// Name of the network, requests to `/<name>` are proxied to its nodes
+    pub name: String,
+    /// Expected chain as reported by `getblockchaininfo`: `main`, `test`, `signet` or `regtest`
+    pub chain: String,
+    /// Requests with one of these `Host` headers are proxied to its nodes as well
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub coin: Coin,
+    /// Source of reference height for healthcheck, on mainnet coin's public source or `healthcheck.url` if not set.
+    /// Without any, only time since node's last block is checked.
+    pub reference_url: Option<String>,
+    /// Json pointer to height in response of `reference_url`, e.g. `/data/blocks`. Response is a plain number if not set.
+    pub reference_pointer: Option<String>,
+    /// Expected time between blocks in seconds, coin's target block time if not set
+    pub block_time: Option<u64>,
+    /// Lag thresholds of the network, `healthcheck.lag` with seconds scaled by block time if not set
+    pub lag: Option<LagThresholds>,
+    pub nodes: Vec<Node>,
+}
+
+impl Network {
+    pub fn to_nodes(&self) -> BTreeMap<usize, BitcoinNode> {
+        to_nodes(&self.nodes)
+    }
+
+    pub fn block_time(&self) -> u64 {
+        self.block_time.unwrap_or_else(|| self.coin.block_time())
+    }
+
+    /// Reference height source as url and json pointer to height, `None` if there is none for the chain
+    pub fn reference(&self, default_url: &str) -> Option<(String, Option<String>)> {
+        match (&self.reference_url, self.coin.reference()) {
+            (Some(url), _) => Some((url.clone(), self.reference_pointer.clone())),
+            (None, Some((url, pointer))) if self.chain == "main" => Some((url.to_string(), Some(pointer.to_string()))),
+            // mainnet heights would make testnet nodes look ahead and the rest behind
+            (None, None) if self.chain == "main" => Some((default_url.to_string(), None)),
+            (None, _) => None,
+        }
+    }
+
+    /// Lag thresholds of the network, default ones expect bitcoin's 10 minute blocks
+    pub fn lag(&self, default: &LagThresholds) -> LagThresholds {
+        if let Some(ref lag) = self.lag {
+            return lag.clone();
+        }
+        let scale = |seconds: i64| seconds * self.block_time() as i64 / Coin::Bitcoin.block_time() as i64;
+        LagThresholds {
+            warning_seconds: default.warning_seconds.map(scale),
+            quarantine_seconds: default.quarantine_seconds.map(scale),
+            ..default.clone()
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -46,10 +368,112 @@ pub struct OpsGenie {
     pub team: String,
 }
 
+/// Alert sinks in addition to `[opsgenie]` (named `opsgenie` in routes) and rules which alerts go where
+#[derive(Debug, Deserialize, Clone)]
+pub struct Alerts {
+    /// Interval after which still active alert is sent again, in seconds
+    pub repeat_interval: Option<u64>,
+    #[serde(default)]
+    pub sinks: Vec<AlertSinkConfig>,
+    /// If empty, every alert goes to every sink
+    #[serde(default)]
+    pub routes: Vec<AlertRoute>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AlertSinkConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: AlertSinkKind,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AlertSinkKind {
+    /// Slack or Mattermost incoming webhook
+    Slack { url: String },
+    /// PagerDuty Events API v2
+    PagerDuty { routing_key: String, url: Option<String> },
+    /// Alert posted as json, signed if `secret` is set
+    Webhook { url: String, secret: Option<String> },
+    Email {
+        host: String,
+        port: u16,
+        /// Use STARTTLS
+        tls: bool,
+        username: Option<String>,
+        password: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AlertRoute {
+    /// Alert kinds matched by the route, all if empty
+    #[serde(default)]
+    pub kinds: Vec<AlertKind>,
+    #[serde(default)]
+    pub min_severity: AlertSeverity,
+    pub sinks: Vec<String>,
+}
+
+/// Admin API is disabled if the section is missing
+#[derive(Debug, Deserialize, Clone)]
+pub struct Admin {
+    /// Expected in `Authorization: Bearer <token>` header
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Telemetry {
+    /// OTLP/HTTP traces endpoint of OpenTelemetry collector, e.g. `http://localhost:4318/v1/traces`
+    pub endpoint: String,
+    pub service_name: String,
+    /// Interval between exports of finished spans, in seconds
+    pub export_interval: u64,
+    /// Finished spans over this number are dropped until the next export
+    pub max_queue: usize,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Audit {
+    /// Path to append-only log of wallet-affecting rpc calls, one json record per line
+    pub path: String,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Server {
     pub host: String,
     pub port: String,
+    /// Plain http is served if not set
+    pub tls: Option<ServerTls>,
+    /// Time between readiness turning false and closing the listener on shutdown, in seconds
+    #[serde(default = "default_shutdown_delay")]
+    pub shutdown_delay: u64,
+    /// Time given to in-flight requests to finish on shutdown, in seconds
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+}
+
+fn default_shutdown_delay() -> u64 {
+    5
+}
+
+fn default_shutdown_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ServerTls {
+    /// PEM file with certificate chain
+    pub cert_path: String,
+    /// PEM file with PKCS8 or RSA private key
+    pub key_path: String,
+    /// PEM file with CA certificates, if set clients must present a certificate signed by one of them
+    pub client_ca_path: Option<String>,
+    /// Interval between reloads of certificate files, in seconds, files are not reloaded if not set
+    pub reload_interval: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -72,19 +496,25 @@ impl Config {
     }
 
     pub fn to_nodes(&self) -> BTreeMap<usize, BitcoinNode> {
-        self.nodes
-            .iter()
-            .enumerate()
-            .map(|(i, node)| {
-                (
-                    i,
-                    BitcoinNode::new(
-                        node.bitcoin_rpc_url.clone(),
-                        node.bitcoin_rpc_user.clone(),
-                        node.bitcoin_rpc_password.clone(),
-                    ),
-                )
-            })
-            .collect()
+        to_nodes(&self.nodes)
     }
 }
+
+fn to_nodes(nodes: &[Node]) -> BTreeMap<usize, BitcoinNode> {
+    nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            (
+                i,
+                BitcoinNode::new(
+                    node.bitcoin_rpc_url.clone(),
+                    node.bitcoin_rpc_user.clone(),
+                    node.password(),
+                    node.bitcoin_rpc_cookie_file.clone(),
+                    node.zmq_url.clone(),
+                ),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,183 @@
+mod error;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use failure::Fail;
+use futures::future::{self, Either};
+use futures::prelude::*;
+
+pub use self::error::*;
+use client::{BitcoinClient, BitcoinClientImpl, HttpClient, MempoolClient, MempoolClientImpl};
+use config::Config;
+use config::Fees as FeesConfig;
+use models::*;
+use utils::log_warn;
+
+/// Aggregates `estimatesmartfee` of all healthy nodes and optional mempool based estimates.
+/// Results are cached until a new block arrives.
+#[derive(Clone)]
+pub struct FeeEstimator {
+    config: FeesConfig,
+    http_client: Arc<HttpClient>,
+    nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    mempool_client: Option<MempoolClientImpl>,
+    cache: Arc<Mutex<Option<FeeEstimates>>>,
+}
+
+impl FeeEstimator {
+    pub fn new(config: &Config, http_client: Arc<HttpClient>, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>) -> Self {
+        let mempool_client = config
+            .fees
+            .mempool_url
+            .clone()
+            .map(|url| MempoolClientImpl::new(http_client.clone(), url));
+        Self {
+            config: config.fees.clone(),
+            http_client,
+            nodes,
+            mempool_client,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn estimate(&self) -> Box<Future<Item = FeeEstimates, Error = Error> + Send> {
+        let self_clone = self.clone();
+        let main_node = {
+            let mut nodes = self.nodes.lock().unwrap();
+            active_node(&mut nodes)
+        };
+        Box::new(
+            self.bitcoin_client(&main_node)
+                .get_block_count()
+                .map_err(ectx!(ErrorContext::BitcoinNode, ErrorKind::Internal => main_node.url))
+                .and_then(move |height| {
+                    let cached = self_clone.cache.lock().unwrap().clone();
+                    match cached {
+                        Some(ref estimates) if estimates.height == height => Either::A(future::ok(estimates.clone())),
+                        _ => Either::B(self_clone.collect(height)),
+                    }
+                }),
+        )
+    }
+
+    fn collect(&self, height: u64) -> impl Future<Item = FeeEstimates, Error = Error> + Send {
+        let nodes = {
+            let mut nodes = self.nodes.lock().unwrap();
+            let healthy = healthy_nodes(&nodes);
+            if healthy.is_empty() {
+                vec![active_node(&mut nodes)]
+            } else {
+                healthy
+            }
+        };
+        let targets = self.config.targets.clone();
+        let node_estimates: Vec<_> = nodes
+            .into_iter()
+            .map(|node| {
+                let client = self.bitcoin_client(&node);
+                let estimates: Vec<_> = targets
+                    .iter()
+                    .map(|&target| client.estimate_smart_fee(target).map(move |rate| (target, rate)))
+                    .collect();
+                let url = node.url;
+                future::join_all(estimates).then(move |r| match r {
+                    Ok(rates) => Ok::<_, Error>(
+                        rates
+                            .into_iter()
+                            .filter_map(|(target, rate)| {
+                                rate.map(|rate| {
+                                    let source = FeeSource {
+                                        source: url.clone(),
+                                        fee_rate: btc_per_kvb_to_sat_per_vb(rate),
+                                    };
+                                    (target, source)
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    ),
+                    Err(e) => {
+                        log_warn(&e);
+                        Ok(Vec::new())
+                    }
+                })
+            })
+            .collect();
+        let mempool_estimates = match self.mempool_client {
+            Some(ref client) => {
+                let url = self.config.mempool_url.clone().unwrap_or_default();
+                let targets = targets.clone();
+                Either::A(client.get_recommended_fees().then(move |r| {
+                    match r {
+                        Ok(fees) => Ok::<_, Error>(
+                            targets
+                                .into_iter()
+                                .map(|target| {
+                                    let source = FeeSource {
+                                        source: url.clone(),
+                                        fee_rate: fees.for_target(target),
+                                    };
+                                    (target, source)
+                                })
+                                .collect::<Vec<_>>(),
+                        ),
+                        Err(e) => {
+                            log_warn(&e);
+                            Ok(Vec::new())
+                        }
+                    }
+                }))
+            }
+            None => Either::B(future::ok(Vec::new())),
+        };
+        let config = self.config.clone();
+        let cache = self.cache.clone();
+        future::join_all(node_estimates)
+            .join(mempool_estimates)
+            .and_then(move |(node_estimates, mempool_estimates)| {
+                let sources: Vec<(u16, FeeSource)> = node_estimates.into_iter().flat_map(|e| e).chain(mempool_estimates).collect();
+                let estimates: Vec<FeeEstimate> = targets
+                    .into_iter()
+                    .filter_map(|target| {
+                        let sources: Vec<FeeSource> = sources
+                            .iter()
+                            .filter(|(t, _)| *t == target)
+                            .map(|(_, source)| source.clone())
+                            .collect();
+                        median(sources.iter().map(|source| source.fee_rate).collect()).map(|rate| FeeEstimate {
+                            target,
+                            fee_rate: (rate.ceil() as u64).max(config.min_fee_rate).min(config.max_fee_rate),
+                            sources,
+                        })
+                    })
+                    .collect();
+                if estimates.is_empty() {
+                    return Err(ErrorKind::NoEstimates.into());
+                }
+                let estimates = FeeEstimates { height, estimates };
+                *cache.lock().unwrap() = Some(estimates.clone());
+                Ok(estimates)
+            })
+    }
+
+    fn bitcoin_client(&self, node: &BitcoinNode) -> BitcoinClientImpl {
+        BitcoinClientImpl::from_node(self.http_client.clone(), node)
+    }
+}
+
+fn btc_per_kvb_to_sat_per_vb(rate: f64) -> f64 {
+    rate * 100_000_000.0 / 1000.0
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(::std::cmp::Ordering::Equal));
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    } else {
+        Some(values[mid])
+    }
+}
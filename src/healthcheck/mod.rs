@@ -0,0 +1,377 @@
+//! Periodic check of the active node against reference height, quarantining nodes that fall behind.
+
+mod error;
+mod state;
+
+pub use self::state::StateStore;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chrono;
+use futures::future;
+use futures::prelude::*;
+use tokio::timer::Interval;
+use tokio_core;
+
+use alerts::Alerter;
+use client::{BitcoinClient, BitcoinClientImpl, Block, BlockchainInfoClient, BlockchainInfoClientImpl, HttpClient};
+use config::{Config, Healthcheck as HealthcheckConfig, LagThresholds, Network as NetworkConfig};
+use models::*;
+use shutdown::Shutdown;
+use utils::log_error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LagLevel {
+    Ok,
+    Warning,
+    Quarantine,
+}
+
+/// How far node is behind, `blocks` is unknown if reference height is not available
+#[derive(Debug, Clone, Copy)]
+struct Lag {
+    blocks: Option<u64>,
+    seconds: i64,
+}
+
+impl Lag {
+    fn new(block: &Block, reference_height: Option<u64>, now: i64) -> Self {
+        Self {
+            // node ahead of reference is not lagging, it's the reference that is late
+            blocks: reference_height.map(|height| height.saturating_sub(block.height)),
+            seconds: now - block.time as i64,
+        }
+    }
+
+    fn level(&self, thresholds: &LagThresholds) -> LagLevel {
+        let over = |blocks: u64, seconds: Option<i64>| {
+            self.blocks.map(|lag| lag >= blocks).unwrap_or(false) || seconds.map(|seconds| self.seconds >= seconds).unwrap_or(false)
+        };
+        if over(thresholds.quarantine_blocks, thresholds.quarantine_seconds) {
+            LagLevel::Quarantine
+        } else if over(thresholds.warning_blocks, thresholds.warning_seconds) {
+            LagLevel::Warning
+        } else {
+            LagLevel::Ok
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Healthcheck {
+    config: HealthcheckConfig,
+    nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    http_client: Arc<HttpClient>,
+    /// Source of reference height, block lag is not checked without it
+    reference: Option<BlockchainInfoClientImpl>,
+    alerter: Alerter,
+    state: Option<StateStore>,
+    /// Name of the network, `None` for default nodes
+    network: Option<String>,
+}
+
+impl Healthcheck {
+    /// Healthcheck of default nodes if `network` is `None`, otherwise of nodes of the network
+    pub fn new<C: HttpClient + Clone>(
+        config: &Config,
+        network: Option<&NetworkConfig>,
+        nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+        client: C,
+    ) -> Self {
+        let name = network.map(|network| network.name.clone());
+        let mut healthcheck_config = config.healthcheck.clone();
+        let reference = match network {
+            Some(network) => {
+                healthcheck_config.lag = network.lag(&config.healthcheck.lag);
+                let reference = network.reference(&config.healthcheck.url);
+                if reference.is_none() {
+                    warn!(
+                        "No reference height for {} network on {} chain, only time since last block is checked",
+                        network.name, network.chain
+                    );
+                }
+                reference
+            }
+            None => Some((config.healthcheck.url.clone(), None)),
+        };
+        Self {
+            config: healthcheck_config,
+            nodes,
+            reference: reference.map(|(url, pointer)| BlockchainInfoClientImpl::new(url, pointer, client.clone())),
+            alerter: Alerter::new(config, client.clone()),
+            state: config
+                .healthcheck
+                .state
+                .clone()
+                .map(|state| StateStore::new(state, name.as_ref().map(|name| name.as_str()))),
+            network: name,
+            http_client: Arc::new(client),
+        }
+    }
+
+    /// Releases nodes from quarantine to probation and checks the active node and nodes on probation
+    pub fn check(&self) -> Box<Future<Item = (), Error = ()> + Send> {
+        match self.network {
+            Some(ref network) => info!("Started healthcheck of {} network", network),
+            None => info!("Started healthcheck"),
+        }
+        self.release_quarantined();
+        let (node, probation) = {
+            let mut nodes = self.nodes.lock().unwrap();
+            (active_node(&mut nodes), probation_nodes(&nodes))
+        };
+        let client = self.client(&node);
+        let self_clone = self.clone();
+        let alerter = self.alerter.clone();
+        let network = self.network.clone();
+        let reference_height = match self.reference {
+            Some(ref reference) => future::Either::A(reference.get_block_count().map(Some)),
+            None => future::Either::B(future::ok(None)),
+        };
+        Box::new(reference_height.then(move |r| {
+            let reference_alert = match r {
+                Ok(_) => alerter.resolve(AlertKind::ReferenceUnavailable, network.as_ref().map(|network| network.as_str())),
+                Err(ref e) => alerter.notify(Alert::new(
+                    AlertKind::ReferenceUnavailable,
+                    AlertSeverity::Warning,
+                    format!("Couldn't get last block from blockchain info - {}", e),
+                    network,
+                )),
+            };
+            // without reference height node is still checked for time since its last block
+            let reference_height = r.ok().and_then(|height| height);
+            let probation_checks: Vec<_> = probation
+                .into_iter()
+                .map(|node| {
+                    let self_clone = self_clone.clone();
+                    self_clone
+                        .client(&node)
+                        .get_best_block()
+                        .then(move |r| self_clone.check_probation(&node, reference_height, r))
+                })
+                .collect();
+            let node_check = client
+                .get_best_block()
+                .then(move |r| self_clone.check_node(&node, reference_height, r));
+            reference_alert.join3(node_check, future::join_all(probation_checks)).map(|_| ())
+        }))
+    }
+
+    fn check_node<E: ::std::fmt::Display>(
+        &self,
+        node: &BitcoinNode,
+        reference_height: Option<u64>,
+        block: Result<Block, E>,
+    ) -> Box<Future<Item = (), Error = ()> + Send> {
+        let url = node.url.clone();
+        let block = match block {
+            Ok(block) => block,
+            Err(e) => {
+                return self.alerter.notify(Alert::new(
+                    AlertKind::NodeUnreachable,
+                    AlertSeverity::Critical,
+                    format!("Couldn't get last block from bitcoin node {} - {}", url, e),
+                    Some(url.clone()),
+                ))
+            }
+        };
+        let resolve_unreachable = self.alerter.resolve(AlertKind::NodeUnreachable, Some(&url));
+        let lag = Lag::new(&block, reference_height, chrono::Utc::now().timestamp());
+        let level = lag.level(&self.config.lag);
+        let (failures, quarantined, healthy) = {
+            let mut nodes = self.nodes.lock().unwrap();
+            let (failures, quarantined) = {
+                let n = match nodes.values_mut().find(|n| n.url == url) {
+                    Some(n) => n,
+                    None => return resolve_unreachable,
+                };
+                n.last_height = Some(block.height);
+                n.failures = if level == LagLevel::Quarantine { n.failures + 1 } else { 0 };
+                let quarantined = n.failures >= self.config.lag.grace_checks.max(1);
+                if quarantined {
+                    self.quarantine(n, format!("lag of {:?} blocks, {} seconds", lag.blocks, lag.seconds));
+                }
+                (n.failures, quarantined)
+            };
+            (failures, quarantined, healthy_nodes(&nodes).len())
+        };
+        let describe = || {
+            format!(
+                "Bitcoin node {} at height {} is {} blocks behind reference, last block {} seconds ago",
+                url,
+                block.height,
+                lag.blocks.map(|blocks| blocks.to_string()).unwrap_or_else(|| "unknown".to_string()),
+                lag.seconds
+            )
+        };
+        let lag_alert = if quarantined {
+            warn!("Quarantined node: {}", describe());
+            // with one healthy node left there is nothing to fail over to
+            let severity = if healthy < 2 {
+                AlertSeverity::Critical
+            } else {
+                AlertSeverity::Warning
+            };
+            self.alerter.notify(Alert::new(
+                AlertKind::NodeLag,
+                severity,
+                format!("{}, delay from blockchain exceeded limit.", describe()),
+                Some(url.clone()),
+            ))
+        } else if level == LagLevel::Ok {
+            self.alerter.resolve(AlertKind::NodeLag, Some(&url))
+        } else {
+            if level == LagLevel::Quarantine {
+                warn!("Node lag over quarantine threshold, grace check {}: {}", failures, describe());
+            }
+            self.alerter.notify(Alert::new(
+                AlertKind::NodeLag,
+                AlertSeverity::Warning,
+                describe(),
+                Some(url.clone()),
+            ))
+        };
+        Box::new(resolve_unreachable.join(lag_alert).map(|_| ()))
+    }
+
+    /// Node on probation is quarantined again on the first failed check and fully trusted after enough passed ones,
+    /// resolving its alerts
+    fn check_probation<E: ::std::fmt::Display>(
+        &self,
+        node: &BitcoinNode,
+        reference_height: Option<u64>,
+        block: Result<Block, E>,
+    ) -> Box<Future<Item = (), Error = ()> + Send> {
+        let reason = match block {
+            Ok(ref block) => {
+                let lag = Lag::new(block, reference_height, chrono::Utc::now().timestamp());
+                if lag.level(&self.config.lag) == LagLevel::Quarantine {
+                    Some(format!("lag of {:?} blocks, {} seconds on probation", lag.blocks, lag.seconds))
+                } else {
+                    None
+                }
+            }
+            Err(ref e) => Some(format!("unreachable on probation - {}", e)),
+        };
+        let mut nodes = self.nodes.lock().unwrap();
+        let n = match nodes.values_mut().find(|n| n.url == node.url) {
+            Some(n) => n,
+            None => return Box::new(future::ok(())),
+        };
+        if let Ok(ref block) = block {
+            n.last_height = Some(block.height);
+        }
+        let passed = match (n.quarantine.clone(), reason) {
+            (Quarantine::Probation(_), Some(reason)) => {
+                warn!("Node {} failed healthcheck on probation: {}", n.url, reason);
+                self.quarantine(n, reason);
+                return Box::new(future::ok(()));
+            }
+            (Quarantine::Probation(passed), None) => passed + 1,
+            // state changed while node was checked
+            _ => return Box::new(future::ok(())),
+        };
+        if passed >= self.config.probation.checks {
+            info!("Node {} passed probation", n.url);
+            n.quarantine = Quarantine::No;
+            n.record(chrono::Utc::now().naive_utc(), NodeEventKind::Recovered);
+            let url = n.url.clone();
+            Box::new(
+                self.alerter
+                    .resolve(AlertKind::NodeLag, Some(&url))
+                    .join(self.alerter.resolve(AlertKind::NodeUnreachable, Some(&url)))
+                    .map(|_| ()),
+            )
+        } else {
+            n.quarantine = Quarantine::Probation(passed);
+            Box::new(future::ok(()))
+        }
+    }
+
+    /// Quarantines node for twice as long as the previous time, unless it was healthy for `backoff_reset` since then
+    fn quarantine(&self, n: &mut BitcoinNode, reason: String) {
+        let now = chrono::Utc::now().naive_utc();
+        let reset = n
+            .last_quarantine_end
+            .map(|end| now - end > chrono::Duration::seconds(self.config.backoff_reset))
+            .unwrap_or(true);
+        n.quarantine_level = if reset { 0 } else { n.quarantine_level + 1 };
+        let seconds = self
+            .config
+            .quarantine
+            .checked_mul(1 << n.quarantine_level.min(30))
+            .unwrap_or(self.config.max_quarantine)
+            .min(self.config.max_quarantine);
+        let until = now + chrono::Duration::seconds(seconds);
+        info!("Quarantined node {} until {}: {}", n.url, until, reason);
+        n.quarantine = Quarantine::Yes(until);
+        n.main = false;
+        n.failures = 0;
+        n.last_quarantine_end = Some(until);
+        n.record(
+            now,
+            NodeEventKind::Quarantined {
+                until,
+                level: n.quarantine_level,
+                reason,
+            },
+        );
+    }
+
+    fn release_quarantined(&self) {
+        let now = chrono::Utc::now().naive_utc();
+        let mut nodes = self.nodes.lock().unwrap();
+        for n in nodes.values_mut() {
+            let release = match n.quarantine {
+                Quarantine::Yes(until) => now >= until,
+                _ => false,
+            };
+            if release {
+                info!("Released node {} from quarantine to probation", n.url);
+                n.quarantine = Quarantine::Probation(0);
+                n.record(now, NodeEventKind::Probation);
+            }
+        }
+    }
+
+    /// Persists node state, if configured, so that it survives restart
+    fn save_state(&self) {
+        if let Some(ref state) = self.state {
+            let nodes = self.nodes.lock().unwrap();
+            if let Err(e) = state.save(&nodes) {
+                log_error(&e);
+            }
+        }
+    }
+
+    fn client(&self, node: &BitcoinNode) -> BitcoinClientImpl {
+        BitcoinClientImpl::from_node(self.http_client.clone(), node)
+    }
+}
+
+/// Spawns a thread that runs healthcheck every `healthcheck.timeout` seconds until shutdown
+pub fn start(healthcheck: Healthcheck, shutdown: Shutdown) -> JoinHandle<()> {
+    let interval = Duration::from_secs(healthcheck.config.timeout);
+    thread::spawn(move || {
+        let mut core = tokio_core::reactor::Core::new().unwrap();
+        let healthcheck_clone = healthcheck.clone();
+        let checks = Interval::new(Instant::now(), interval)
+            .map_err(|e| {
+                error!("Error creating interval {}", e);
+            })
+            .for_each(move |_| {
+                let healthcheck = healthcheck.clone();
+                healthcheck.check().then(move |_| {
+                    healthcheck.save_state();
+                    future::ok(())
+                })
+            });
+        // check in progress is abandoned, state of the last finished one is saved again
+        let _ = core.run(checks.select2(shutdown.wait()));
+        healthcheck_clone.save_state();
+        info!("Stopped healthcheck");
+    })
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{self, NaiveDateTime};
+use failure::Fail;
+use serde_json;
+
+use super::error::*;
+use config::NodeState as NodeStateConfig;
+use models::*;
+
+/// Snapshot of nodes health written after every healthcheck
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct NodeStates {
+    saved_at: NaiveDateTime,
+    nodes: Vec<BitcoinNode>,
+}
+
+/// Keeps quarantine, failure counters and last heights of nodes in a json file,
+/// so that a restart doesn't send traffic to a node that was just quarantined
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    config: NodeStateConfig,
+}
+
+impl StateStore {
+    /// State of additional network is kept next to the configured file, e.g. `node_state.testnet.json`
+    pub fn new(mut config: NodeStateConfig, network: Option<&str>) -> Self {
+        if let Some(network) = network {
+            let path = Path::new(&config.path);
+            let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+            let file_name = match path.extension() {
+                Some(extension) => format!("{}.{}.{}", stem, network, extension.to_string_lossy()),
+                None => format!("{}.{}", stem, network),
+            };
+            config.path = path.with_file_name(file_name).to_string_lossy().to_string();
+        }
+        Self { config }
+    }
+
+    /// Writes state of nodes to a temporary file and renames it, so that the file is never left half written
+    pub fn save(&self, nodes: &BTreeMap<usize, BitcoinNode>) -> Result<(), Error> {
+        let path = &self.config.path;
+        let states = NodeStates {
+            saved_at: chrono::Utc::now().naive_utc(),
+            nodes: nodes.values().cloned().collect(),
+        };
+        let data = serde_json::to_vec(&states).map_err(ectx!(try ErrorContext::SaveState, ErrorSource::Json, ErrorKind::Internal))?;
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, data)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(ectx!(ErrorContext::SaveState, ErrorSource::Io, ErrorKind::Internal => path))
+    }
+
+    /// Copies saved state onto nodes from config, matching them by url.
+    /// State older than `max_age` seconds, as well as state of nodes no longer in config, is ignored.
+    pub fn restore(&self, nodes: &mut BTreeMap<usize, BitcoinNode>) -> Result<(), Error> {
+        let path = &self.config.path;
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("No saved node state at {}", path);
+                return Ok(());
+            }
+            Err(e) => return Err(ectx!(err e, ErrorContext::LoadState, ErrorSource::Io, ErrorKind::Internal => path)),
+        };
+        let states: NodeStates =
+            serde_json::from_slice(&data).map_err(ectx!(try ErrorContext::LoadState, ErrorSource::Json, ErrorKind::Internal => path))?;
+        let age = chrono::Utc::now().naive_utc() - states.saved_at;
+        if age > chrono::Duration::seconds(self.config.max_age) {
+            info!("Ignoring node state saved {} seconds ago", age.num_seconds());
+            return Ok(());
+        }
+        for saved in states.nodes {
+            if let Some(n) = nodes.values_mut().find(|n| n.url == saved.url) {
+                n.quarantine = saved.quarantine;
+                n.main = saved.main;
+                n.failures = saved.failures;
+                n.last_height = saved.last_height;
+                n.quarantine_level = saved.quarantine_level;
+                n.last_quarantine_end = saved.last_quarantine_end;
+                n.history = saved.history;
+            }
+        }
+        info!("Restored node state saved {} seconds ago", age.num_seconds());
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use failure::{Backtrace, Context, Fail};
+use std::fmt;
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "indexer error - internal error")]
+    Internal,
+    #[fail(display = "indexer error - reorg is deeper than stored undo data")]
+    ReorgTooDeep,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorSource {
+    #[fail(display = "indexer source - error inside of sled storage")]
+    Sled,
+    #[fail(display = "indexer source - error serializing or parsing json")]
+    Json,
+    #[fail(display = "indexer source - error parsing bytes to utf8")]
+    Utf8,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorContext {
+    #[fail(display = "indexer context - error communicating with bitcoin node")]
+    BitcoinNode,
+    #[fail(display = "indexer context - error opening index storage")]
+    Open,
+    #[fail(display = "indexer context - missing undo data for block")]
+    Undo,
+}
+
+derive_error_impls!();
@@ -0,0 +1,124 @@
+mod error;
+mod storage;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use failure::Fail;
+use futures::future::{self, Either, Loop};
+use futures::prelude::*;
+use tokio::timer::Interval;
+use tokio_core;
+
+pub use self::error::*;
+pub use self::storage::*;
+use client::{BitcoinClient, BitcoinClientImpl, HttpClient};
+use config::Indexer as IndexerConfig;
+use models::*;
+use utils::log_error;
+
+/// Follows the best chain of the active node and feeds its blocks into `IndexStorage`
+#[derive(Clone)]
+pub struct Indexer {
+    storage: Arc<IndexStorage>,
+    nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    http_client: Arc<HttpClient>,
+    start_height: u64,
+}
+
+impl Indexer {
+    pub fn new(
+        config: &IndexerConfig,
+        storage: Arc<IndexStorage>,
+        nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+        http_client: Arc<HttpClient>,
+    ) -> Self {
+        Self {
+            storage,
+            nodes,
+            http_client,
+            start_height: config.start_height,
+        }
+    }
+
+    /// Connects blocks until the index reaches the tip of the active node
+    pub fn sync(&self) -> impl Future<Item = (), Error = Error> + Send {
+        let self_clone = self.clone();
+        future::loop_fn((), move |_| {
+            self_clone
+                .step()
+                .map(|caught_up| if caught_up { Loop::Break(()) } else { Loop::Continue(()) })
+        })
+    }
+
+    /// Connects next block or disconnects the tip on reorg. Resolves to `true` once there is nothing left to connect.
+    fn step(&self) -> Box<Future<Item = bool, Error = Error> + Send> {
+        let client = self.bitcoin_client();
+        let client_clone = client.clone();
+        let storage = self.storage.clone();
+        let tip = match storage.tip() {
+            Ok(tip) => tip,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let next_height = tip.as_ref().map(|tip| tip.height + 1).unwrap_or(self.start_height);
+        Box::new(
+            client
+                .get_block_count()
+                .map_err(ectx!(ErrorContext::BitcoinNode, ErrorKind::Internal))
+                .and_then(move |node_height| {
+                    if next_height > node_height {
+                        return Either::A(future::ok(true));
+                    }
+                    Either::B(
+                        client
+                            .get_block_hash(next_height)
+                            .and_then(move |hash| client_clone.get_verbose_block(hash))
+                            .map_err(ectx!(ErrorContext::BitcoinNode, ErrorKind::Internal => next_height))
+                            .and_then(move |block| match tip {
+                                Some(ref tip) if block.previousblockhash.as_ref() != Some(&tip.hash) => {
+                                    warn!("Index reorg at height {}, disconnecting block {}", tip.height, tip.hash);
+                                    storage.disconnect_tip().map(|_| false)
+                                }
+                                _ => {
+                                    debug!("Indexing block {} at height {}", block.hash, block.height);
+                                    storage.connect_block(&block).map(|_| false)
+                                }
+                            }),
+                    )
+                }),
+        )
+    }
+
+    fn bitcoin_client(&self) -> BitcoinClientImpl {
+        let node = {
+            let mut nodes = self.nodes.lock().unwrap();
+            active_node(&mut nodes)
+        };
+        BitcoinClientImpl::from_node(self.http_client.clone(), &node)
+    }
+}
+
+/// Spawns a thread that keeps index in sync with the chain
+pub fn start<C: HttpClient>(config: IndexerConfig, storage: Arc<IndexStorage>, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>, client: C) {
+    let interval = Duration::from_secs(config.interval);
+    thread::spawn(move || {
+        let mut core = tokio_core::reactor::Core::new().unwrap();
+        let indexer = Indexer::new(&config, storage, nodes, Arc::new(client));
+        core.run(
+            Interval::new(Instant::now(), interval)
+                .map_err(|e| {
+                    error!("Error creating interval {}", e);
+                })
+                .for_each(move |_| {
+                    indexer.sync().then(|r| {
+                        if let Err(e) = r {
+                            log_error(&e);
+                        }
+                        Ok(())
+                    })
+                }),
+        )
+    });
+}
@@ -0,0 +1,307 @@
+use std::collections::{BTreeSet, HashMap};
+use std::str;
+
+use failure::Fail;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use sled::{self, Batch, Db};
+
+use super::error::*;
+use client::VerboseBlock;
+use models::*;
+
+/// Number of blocks for which undo data is kept, i.e. the deepest reorg the index can roll back
+const UNDO_DEPTH: u64 = 100;
+
+const TIP_KEY: &str = "m:tip";
+const START_KEY: &str = "m:start";
+
+/// Last block connected to the index
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct IndexTip {
+    pub height: u64,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct StoredOutput {
+    txid: String,
+    vout: u32,
+    address: String,
+    value: u64,
+    height: u64,
+    spent_by: Option<String>,
+}
+
+/// Everything needed to disconnect a block from the index
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct BlockUndo {
+    hash: String,
+    previous: Option<IndexTip>,
+    created: Vec<StoredOutput>,
+    /// Outputs from earlier blocks spent in this block, in their unspent state
+    spent: Vec<StoredOutput>,
+    history: Vec<String>,
+}
+
+/// Outputs and spends per address, stored in an embedded sled database.
+///
+/// Everything lives in one tree with prefixed keys, so that connecting or disconnecting a block
+/// is a single atomic batch:
+/// - `o:<txid>:<vout>` - output
+/// - `a:<address>:<txid>:<vout>` - output belonging to address
+/// - `t:<address>:<height>:<txid>` - transaction touching address
+/// - `u:<height>` - undo data for block
+/// - `m:tip` - last connected block
+/// - `m:start` - height of the first connected block
+pub struct IndexStorage {
+    db: Db,
+}
+
+impl IndexStorage {
+    pub fn open(path: &str) -> Result<Self, Error> {
+        sled::open(path)
+            .map(|db| IndexStorage { db })
+            .map_err(ectx!(ErrorContext::Open, ErrorSource::Sled, ErrorKind::Internal => path))
+    }
+
+    pub fn tip(&self) -> Result<Option<IndexTip>, Error> {
+        self.get_json(TIP_KEY)
+    }
+
+    /// Height of the first indexed block, outputs and spends in earlier blocks are missing from the index
+    pub fn indexed_from(&self) -> Result<Option<u64>, Error> {
+        self.get_json(START_KEY)
+    }
+
+    pub fn connect_block(&self, block: &VerboseBlock) -> Result<(), Error> {
+        let previous = self.tip()?;
+        let mut batch = Batch::default();
+        if previous.is_none() {
+            batch.insert(START_KEY, to_json(&block.height)?);
+        }
+        let mut created: HashMap<String, StoredOutput> = HashMap::new();
+        let mut spent: Vec<StoredOutput> = Vec::new();
+        let mut history: BTreeSet<String> = BTreeSet::new();
+
+        for tx in &block.tx {
+            for input in &tx.vin {
+                let key = match (input.txid.as_ref(), input.vout) {
+                    (Some(txid), Some(vout)) => output_key(txid, vout),
+                    // coinbase
+                    _ => continue,
+                };
+                let spent_in_block = created.get_mut(&key).map(|output| {
+                    output.spent_by = Some(tx.txid.clone());
+                    output.address.clone()
+                });
+                let address = match spent_in_block {
+                    Some(address) => Some(address),
+                    // outputs created before the index start height are unknown and skipped
+                    None => match self.get_json::<StoredOutput>(&key)? {
+                        Some(output) => {
+                            let mut updated = output.clone();
+                            updated.spent_by = Some(tx.txid.clone());
+                            batch.insert(key.as_str(), to_json(&updated)?);
+                            spent.push(output);
+                            Some(updated.address)
+                        }
+                        None => None,
+                    },
+                };
+                if let Some(address) = address {
+                    history.insert(history_key(&address, block.height, &tx.txid));
+                }
+            }
+            for output in &tx.vout {
+                let address = match output.script_pub_key.address() {
+                    Some(address) => address,
+                    None => continue,
+                };
+                history.insert(history_key(&address, block.height, &tx.txid));
+                created.insert(
+                    output_key(&tx.txid, output.n),
+                    StoredOutput {
+                        txid: tx.txid.clone(),
+                        vout: output.n,
+                        address,
+                        value: btc_to_satoshis(output.value),
+                        height: block.height,
+                        spent_by: None,
+                    },
+                );
+            }
+        }
+
+        for (key, output) in &created {
+            batch.insert(key.as_str(), to_json(output)?);
+            batch.insert(address_output_key(&output.address, &output.txid, output.vout).as_str(), &[][..]);
+        }
+        for key in &history {
+            batch.insert(key.as_str(), &[][..]);
+        }
+        let undo = BlockUndo {
+            hash: block.hash.clone(),
+            previous,
+            created: created.into_iter().map(|(_, output)| output).collect(),
+            spent,
+            history: history.into_iter().collect(),
+        };
+        batch.insert(undo_key(block.height).as_str(), to_json(&undo)?);
+        if block.height >= UNDO_DEPTH {
+            batch.remove(undo_key(block.height - UNDO_DEPTH).as_str());
+        }
+        let tip = IndexTip {
+            height: block.height,
+            hash: block.hash.clone(),
+        };
+        batch.insert(TIP_KEY, to_json(&tip)?);
+        self.db
+            .apply_batch(batch)
+            .map_err(ectx!(ErrorSource::Sled, ErrorKind::Internal => block.height, block.hash))
+    }
+
+    /// Rolls back the last connected block, returning the new tip
+    pub fn disconnect_tip(&self) -> Result<Option<IndexTip>, Error> {
+        let tip = match self.tip()? {
+            Some(tip) => tip,
+            None => return Ok(None),
+        };
+        let undo = self
+            .get_json::<BlockUndo>(&undo_key(tip.height))?
+            .ok_or_else(|| ectx!(try err ErrorContext::Undo, ErrorKind::ReorgTooDeep => tip))?;
+        let mut batch = Batch::default();
+        for output in &undo.created {
+            batch.remove(output_key(&output.txid, output.vout).as_str());
+            batch.remove(address_output_key(&output.address, &output.txid, output.vout).as_str());
+        }
+        for output in &undo.spent {
+            batch.insert(output_key(&output.txid, output.vout).as_str(), to_json(output)?);
+        }
+        for key in &undo.history {
+            batch.remove(key.as_str());
+        }
+        batch.remove(undo_key(tip.height).as_str());
+        match undo.previous {
+            Some(ref previous) => batch.insert(TIP_KEY, to_json(previous)?),
+            None => {
+                batch.remove(TIP_KEY);
+                batch.remove(START_KEY);
+            }
+        }
+        self.db
+            .apply_batch(batch)
+            .map_err(ectx!(try ErrorSource::Sled, ErrorKind::Internal => tip))?;
+        Ok(undo.previous)
+    }
+
+    pub fn utxo(&self, address: &str) -> Result<Vec<Utxo>, Error> {
+        Ok(self
+            .outputs(address)?
+            .into_iter()
+            .filter(|output| output.spent_by.is_none())
+            .map(|output| Utxo {
+                txid: output.txid,
+                vout: output.vout,
+                value: output.value,
+                height: output.height,
+            })
+            .collect())
+    }
+
+    pub fn balance(&self, address: &str) -> Result<AddressBalance, Error> {
+        let outputs = self.outputs(address)?;
+        let received = outputs.iter().map(|output| output.value).sum();
+        let unspent: Vec<&StoredOutput> = outputs.iter().filter(|output| output.spent_by.is_none()).collect();
+        let balance = unspent.iter().map(|output| output.value).sum();
+        let indexed_height = self.tip()?.map(|tip| tip.height);
+        let indexed_from = self.indexed_from()?;
+        Ok(AddressBalance {
+            address: address.to_string(),
+            balance,
+            received,
+            sent: received - balance,
+            utxo_count: unspent.len(),
+            indexed_height,
+            indexed_from,
+        })
+    }
+
+    /// Transactions touching address, newest first
+    pub fn transactions(&self, address: &str) -> Result<Vec<AddressTransaction>, Error> {
+        let prefix = format!("t:{}:", address);
+        let mut txs = Vec::new();
+        for key in self.keys(&prefix)? {
+            let mut parts = key[prefix.len()..].splitn(2, ':');
+            let height = parts.next().and_then(|height| height.parse::<u64>().ok());
+            let txid = parts.next();
+            if let (Some(height), Some(txid)) = (height, txid) {
+                txs.push(AddressTransaction {
+                    txid: txid.to_string(),
+                    height,
+                });
+            }
+        }
+        txs.reverse();
+        Ok(txs)
+    }
+
+    fn outputs(&self, address: &str) -> Result<Vec<StoredOutput>, Error> {
+        let prefix = format!("a:{}:", address);
+        let mut outputs = Vec::new();
+        for key in self.keys(&prefix)? {
+            let key = format!("o:{}", &key[prefix.len()..]);
+            if let Some(output) = self.get_json::<StoredOutput>(&key)? {
+                outputs.push(output);
+            }
+        }
+        Ok(outputs)
+    }
+
+    fn keys(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        let mut keys = Vec::new();
+        for item in self.db.scan_prefix(prefix) {
+            let (key, _) = item.map_err(ectx!(try ErrorSource::Sled, ErrorKind::Internal => prefix))?;
+            let key = str::from_utf8(&key).map_err(ectx!(try ErrorSource::Utf8, ErrorKind::Internal => prefix))?;
+            keys.push(key.to_string());
+        }
+        Ok(keys)
+    }
+
+    fn get_json<T>(&self, key: &str) -> Result<Option<T>, Error>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let value = self.db.get(key).map_err(ectx!(try ErrorSource::Sled, ErrorKind::Internal => key))?;
+        match value {
+            Some(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(ectx!(ErrorSource::Json, ErrorKind::Internal => key)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    serde_json::to_vec(value).map_err(ectx!(ErrorSource::Json, ErrorKind::Internal))
+}
+
+fn btc_to_satoshis(value: f64) -> u64 {
+    (value * 100_000_000.0).round() as u64
+}
+
+fn output_key(txid: &str, vout: u32) -> String {
+    format!("o:{}:{}", txid, vout)
+}
+
+fn address_output_key(address: &str, txid: &str, vout: u32) -> String {
+    format!("a:{}:{}:{}", address, txid, vout)
+}
+
+fn history_key(address: &str, height: u64, txid: &str) -> String {
+    format!("t:{}:{:010}:{}", address, height, txid)
+}
+
+fn undo_key(height: u64) -> String {
+    format!("u:{:010}", height)
+}
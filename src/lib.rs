@@ -6,50 +6,76 @@ extern crate failure;
 extern crate futures;
 extern crate futures_cpupool;
 extern crate hyper;
+#[macro_use]
+extern crate lazy_static;
 extern crate serde;
 #[macro_use]
 extern crate serde_json;
 extern crate serde_qs;
+extern crate sha2;
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
 extern crate log;
 extern crate base64;
 extern crate config as config_crate;
-extern crate gelf;
+extern crate hex;
+extern crate hmac;
 extern crate hyper_tls;
+extern crate lettre;
+extern crate lettre_email;
+extern crate native_tls;
 extern crate num;
 extern crate regex;
 extern crate simplelog;
+extern crate sled;
 #[macro_use]
 extern crate sentry;
 extern crate tokio;
 extern crate tokio_core;
+extern crate tokio_rustls;
+extern crate tokio_signal;
+extern crate tungstenite;
 extern crate uuid;
+extern crate zmq;
 
 #[macro_use]
 mod macros;
+mod alerts;
 mod api;
+mod audit;
+mod broadcast;
 mod client;
 mod config;
+mod fees;
+mod healthcheck;
+mod indexer;
 mod logger;
 mod models;
+mod networks;
+mod notifications;
 mod prelude;
+mod redact;
 mod sentry_integration;
+mod shutdown;
+mod telemetry;
+mod tracker;
 mod utils;
+mod watches;
+mod webhooks;
 
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::{Duration, Instant};
-
-use futures::future::Either;
-use futures::{future, Future, Stream};
-use tokio::timer::Interval;
-
-use client::{
-    BitcoinClient, BitcoinClientImpl, BlockchainInfoClient, BlockchainInfoClientImpl, HttpClientImpl, OpsGenieClient, OpsGenieClientImpl,
-};
-use models::*;
+use std::time::Duration;
+
+use audit::AuditLog;
+use client::HttpClientImpl;
+use healthcheck::{Healthcheck, StateStore};
+use indexer::IndexStorage;
+use networks::Networks;
+use notifications::NotificationHub;
+use shutdown::Shutdown;
+use tracker::TxTracker;
+use watches::WatchService;
 
 pub fn hello() {
     println!("Hello world");
@@ -62,137 +88,90 @@ pub fn print_config() {
 pub fn start_server() {
     let config = get_config();
     // Prepare sentry integration
-    let _sentry = sentry_integration::init(config.sentry.as_ref());
+    let sentry_guard = sentry_integration::init(config.sentry.as_ref());
     // Prepare logger
     logger::init(&config);
     // Prepare nodes
-    let nodes = config.to_nodes();
+    let mut nodes = config.to_nodes();
+    let networks = Networks::new(&config);
+    networks::verify(&config, &nodes, &networks, HttpClientImpl::new(&config))
+        .unwrap_or_else(|e| panic!("Error verifying chain of nodes: {}", e));
+    if let Some(ref state_config) = config.healthcheck.state {
+        if let Err(e) = StateStore::new(state_config.clone(), None).restore(&mut nodes) {
+            utils::log_error(&e);
+        }
+        for network in networks.all() {
+            let mut nodes = network.nodes.lock().unwrap();
+            if let Err(e) = StateStore::new(state_config.clone(), Some(&network.config.name)).restore(&mut nodes) {
+                utils::log_error(&e);
+            }
+        }
+    }
     let nodes = Arc::new(Mutex::new(nodes));
-    let interval = Duration::from_secs(config.healthcheck.timeout);
-    let quarantine_time = chrono::Duration::seconds(config.healthcheck.quarantine);
-    let client = HttpClientImpl::new(&config);
-    let nodes_clone = nodes.clone();
-    let config_clone = config.clone();
-
-    thread::spawn(move || {
-        let mut core = tokio_core::reactor::Core::new().unwrap();
-        let opsgenie_client = OpsGenieClientImpl::new(&config, client.clone());
-        let healtcheck_client = BlockchainInfoClientImpl::new(&config, client.clone());
-        let client = Arc::new(client);
-        let nodes_clone2 = nodes_clone.clone();
-        core.run(
-            Interval::new(Instant::now(), interval)
-                .map_err(|e| {
-                    error!("Error creating interval {}", e);
-                })
-                .fold(nodes_clone, |nodes_clone, _| {
-                    info!("Started healthcheck");
-                    healtcheck_client
-                        .get_block_count()
-                        .then(|r| {
-                            let (bitcoin_client, url, i) = {
-                                let mut nodes_ = nodes_clone2.lock().unwrap();
-
-                                let now = chrono::Utc::now().naive_utc();
-
-                                // recovering nodes from quarantine
-                                let quarantine_nodes: Vec<usize> = nodes_
-                                    .iter()
-                                    .filter_map(|(i, n)| {
-                                        if let Quarantine::Yes(t) = n.quarantine {
-                                            if (now - t) > quarantine_time {
-                                                Some(*i)
-                                            } else {
-                                                None
-                                            }
-                                        } else {
-                                            None
-                                        }
-                                    })
-                                    .collect();
-
-                                for i in quarantine_nodes {
-                                    let q_n = nodes_.get_mut(&i).expect("Can not find node in btreemap");
-                                    q_n.quarantine = Quarantine::No;
-                                }
-
-                                let (i, node) = if let Some((i, node)) = nodes_
-                                    .iter()
-                                    .filter(|(_, n)| n.main && n.quarantine == Quarantine::No) //searching for main node not in quarantine
-                                    .nth(0)
-                                    .map(|(i, node)| (*i, node.clone()))
-                                {
-                                    (i, node)
-                                } else if let Some((i, node)) = nodes_
-                                    .iter()
-                                    .filter(|(_, n)| n.quarantine == Quarantine::No) //searching for first node not in quarantine
-                                    .nth(0)
-                                    .map(|(i, n)| (*i, n.clone()))
-                                {
-                                    (i, node)
-                                } else {
-                                    let n = nodes_.get_mut(&0).expect("There is no nodes defined in config");
-                                    n.main = true;
-                                    (0, n.clone()) //if all nodes are in quarantine - take first
-                                };
-
-                                let url = node.url.clone();
-
-                                let client =
-                                    BitcoinClientImpl::new(client.clone(), node.url.clone(), node.user.clone(), node.password.clone());
-
-                                (client, url, i)
-                            };
-
-                            let nodes_clone3 = nodes_clone2.clone();
-
-                            let opsgenie_client = opsgenie_client.clone();
-                            match r {
-                                Ok(count) => Either::A(bitcoin_client.get_last_block().then(move |bl_count| {
-                                    match bl_count {
-                                        Ok(bl_count) => Either::A({
-                                            if count.checked_sub(bl_count).unwrap_or_else(|| u64::max_value()) > 1 {
-                                                let mut nodes = nodes_clone3.lock().unwrap();
-                                                {
-                                                    let n = nodes.get_mut(&i).expect("Can not find node to compare in btreemap");
-                                                    n.quarantine = Quarantine::Yes(chrono::Utc::now().naive_utc());
-                                                    n.main = false;
-                                                }
-                                                Either::A(if nodes.values().filter(|v| v.quarantine == Quarantine::No).count() < 2 {
-                                                    Either::A(
-                                                        opsgenie_client
-                                                            .notify(format!("Bitcoin node {} delay from blockchain exceeded limit.", url))
-                                                            .map_err(|_| ()),
-                                                    )
-                                                } else {
-                                                    Either::B(future::ok(()))
-                                                })
-                                            } else {
-                                                Either::B(future::ok(()))
-                                            }
-                                        }),
-                                        Err(e) => Either::B(
-                                            opsgenie_client
-                                                .notify(format!("Couldn't get last block from bitcoin node {} - {}", url, e))
-                                                .map_err(|_| ()),
-                                        ),
-                                    }
-                                })),
-                                Err(e) => Either::B(
-                                    opsgenie_client
-                                        .notify(format!("Couldn't get last block from blockchain info - {}", e))
-                                        .map_err(|_| ()),
-                                ),
-                            }
-                        })
-                        .then(move |_| future::ok(nodes_clone))
-                })
-                .map(|_| ()),
-        )
+    // Prepare address index
+    let index = config.indexer.clone().map(|indexer_config| {
+        let storage = IndexStorage::open(&indexer_config.path).unwrap_or_else(|e| panic!("Error opening index storage: {}", e));
+        let storage = Arc::new(storage);
+        indexer::start(indexer_config, storage.clone(), nodes.clone(), HttpClientImpl::new(&config));
+        storage
     });
-
-    // Start server
-    api::start_server(config_clone, nodes);
+    // Prepare tracking of broadcasted transactions
+    let tracker = config.tracking.clone().map(|tracking_config| {
+        let tracker = TxTracker::new(tracking_config.clone(), Arc::new(HttpClientImpl::new(&config)), nodes.clone());
+        tracker::start(tracking_config, tracker.clone());
+        tracker
+    });
+    // Prepare relay of ZMQ notifications
+    let hub = NotificationHub::default();
+    if let Some(ref notifications_config) = config.notifications {
+        notifications::start(notifications_config.clone(), nodes.clone(), hub.clone());
+    }
+    // Prepare address and transaction watches
+    let watches = config.watches.clone().map(|watches_config| {
+        let watches = WatchService::new(watches_config.clone(), Arc::new(HttpClientImpl::new(&config)), nodes.clone(), hub);
+        watches::start(watches_config, watches.clone(), HttpClientImpl::new(&config));
+        watches
+    });
+    // Prepare new block webhooks
+    if let Some(ref webhooks_config) = config.webhooks {
+        webhooks::start(webhooks_config.clone(), nodes.clone(), HttpClientImpl::new(&config));
+    }
+    // Prepare audit log of wallet-affecting calls
+    let audit = config
+        .audit
+        .as_ref()
+        .map(|audit_config| AuditLog::open(audit_config).unwrap_or_else(|e| panic!("Error opening audit log: {}", e)));
+    // Prepare healthcheck of nodes
+    let shutdown = Shutdown::new(Duration::from_secs(config.server.shutdown_delay));
+    let healthcheck = Healthcheck::new(&config, None, nodes.clone(), HttpClientImpl::new(&config));
+    let mut healthchecks = vec![healthcheck::start(healthcheck, shutdown.clone())];
+    for network in networks.all() {
+        let healthcheck = Healthcheck::new(&config, Some(&network.config), network.nodes.clone(), HttpClientImpl::new(&config));
+        healthchecks.push(healthcheck::start(healthcheck, shutdown.clone()));
+    }
+
+    // Start server, returns after shutdown or when it failed
+    let services = api::Services {
+        nodes,
+        networks,
+        index,
+        tracker,
+        watches,
+        audit: audit.clone(),
+    };
+    api::start_server(config, services, shutdown.clone());
+    shutdown.start();
+    for healthcheck in healthchecks {
+        let _ = healthcheck.join();
+    }
+    // records of requests served before shutdown
+    if let Some(audit) = audit {
+        audit.flush();
+    }
+    // other background threads are stopped with the process
+    log::logger().flush();
+    // sentry guard sends queued events when dropped
+    drop(sentry_guard);
 }
 
 fn get_config() -> config::Config {
@@ -0,0 +1,60 @@
+//! Fields of the request being handled, e.g. its id, added to records sent to Graylog.
+//! They are set on the thread only while the request's future is polled.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use futures::prelude::*;
+
+thread_local! {
+    static FIELDS: RefCell<BTreeMap<&'static str, String>> = RefCell::new(BTreeMap::new());
+}
+
+/// Future with log fields set while it's polled, nested ones add to fields of the outer
+pub struct WithFields<F> {
+    inner: F,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl<F: Future> Future for WithFields<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let _guard = Guard::set(&self.fields);
+        self.inner.poll()
+    }
+}
+
+pub fn with_fields<F: Future>(fields: Vec<(&'static str, String)>, future: F) -> WithFields<F> {
+    WithFields { inner: future, fields }
+}
+
+/// Fields of the future polled on this thread
+pub fn fields() -> BTreeMap<&'static str, String> {
+    FIELDS.with(|fields| fields.borrow().clone())
+}
+
+/// Restores fields of the outer future when dropped, even if polling panics
+struct Guard {
+    previous: BTreeMap<&'static str, String>,
+}
+
+impl Guard {
+    fn set(fields: &[(&'static str, String)]) -> Self {
+        let previous = FIELDS.with(|current| {
+            let mut current = current.borrow_mut();
+            let previous = current.clone();
+            current.extend(fields.iter().cloned());
+            previous
+        });
+        Guard { previous }
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let previous = ::std::mem::replace(&mut self.previous, BTreeMap::new());
+        FIELDS.with(|current| *current.borrow_mut() = previous);
+    }
+}
@@ -0,0 +1,131 @@
+//! Log file appended to across restarts and rotated by size or time, keeping a limited number of old files.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use chrono::prelude::*;
+
+/// Start of a new file on time based rotation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    Never,
+    Hourly,
+    Daily,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Rotation::Never
+    }
+}
+
+impl Rotation {
+    /// Period the time belongs to, file is rotated when it changes
+    fn period(self, time: DateTime<Utc>) -> Option<String> {
+        match self {
+            Rotation::Never => None,
+            Rotation::Hourly => Some(time.format("%Y-%m-%d %H").to_string()),
+            Rotation::Daily => Some(time.format("%Y-%m-%d").to_string()),
+        }
+    }
+}
+
+/// Delay before retrying rotation that failed, so that errors aren't reported on every write
+const ROTATION_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// File at `path`, with rotated files at `path.1` (newest) to `path.<max_files>` (oldest)
+pub struct RotatingFile {
+    path: String,
+    file: File,
+    size: u64,
+    max_size: Option<u64>,
+    rotation: Rotation,
+    period: Option<String>,
+    max_files: usize,
+    /// Whether the last write ended a line, records are never split between files
+    line_start: bool,
+    rotation_failed_at: Option<Instant>,
+}
+
+impl RotatingFile {
+    pub fn open(path: &str, max_size: Option<u64>, rotation: Rotation, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        // file written before restart belongs to the period it was last modified in
+        let modified = metadata.modified().map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now());
+        Ok(Self {
+            path: path.to_string(),
+            file,
+            size: metadata.len(),
+            max_size,
+            rotation,
+            period: rotation.period(modified),
+            max_files,
+            line_start: true,
+            rotation_failed_at: None,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> String {
+        format!("{}.{}", self.path, index)
+    }
+
+    fn needs_rotation(&self, len: usize) -> bool {
+        if let Some(failed_at) = self.rotation_failed_at {
+            if failed_at.elapsed() < ROTATION_RETRY_DELAY {
+                return false;
+            }
+        }
+        let over_size = self
+            .max_size
+            .map(|max_size| self.size > 0 && self.size + len as u64 > max_size)
+            .unwrap_or(false);
+        over_size || self.rotation.period(Utc::now()) != self.period
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated_path(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if fs::metadata(&from).is_ok() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.size = 0;
+        self.period = self.rotation.period(Utc::now());
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.line_start && self.needs_rotation(buf.len()) {
+            // keep logging to the current file if it can't be rotated
+            match self.rotate() {
+                Ok(_) => self.rotation_failed_at = None,
+                Err(e) => {
+                    eprintln!("Error rotating log file {}: {}", self.path, e);
+                    self.period = self.rotation.period(Utc::now());
+                    self.rotation_failed_at = Some(Instant::now());
+                }
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        self.line_start = buf[..written].ends_with(b"\n");
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
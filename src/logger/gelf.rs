@@ -0,0 +1,279 @@
+//! GELF logger for Graylog over UDP, TCP or TLS. Messages are sent from a background thread,
+//! so a slow or unavailable Graylog never blocks logging; messages over the queue limit are dropped.
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{Level, Log, Metadata, Record};
+use native_tls::{Certificate, TlsConnector};
+use serde_json::{self, Map, Value};
+use uuid::Uuid;
+
+use super::context;
+use super::GrayLogConfig;
+
+/// Messages waiting to be sent, newer ones are dropped when it's full
+const QUEUE_SIZE: usize = 10_000;
+/// Size of UDP chunk including its header, fits common MTU of WAN links
+const UDP_CHUNK_SIZE: usize = 1420;
+const UDP_CHUNK_HEADER_SIZE: usize = 12;
+const UDP_MAX_CHUNKS: usize = 128;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Upper limit for waiting on queued messages when flushing
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GelfTransport {
+    Udp,
+    Tcp,
+    Tls,
+}
+
+impl Default for GelfTransport {
+    fn default() -> Self {
+        GelfTransport::Udp
+    }
+}
+
+pub struct GelfLogger {
+    host: String,
+    /// Static fields of every message, with `_` prefix
+    metadata: Map<String, Value>,
+    sender: Mutex<SyncSender<Vec<u8>>>,
+    /// Number of queued messages not sent yet
+    pending: Arc<AtomicUsize>,
+}
+
+impl GelfLogger {
+    pub fn new(config: &GrayLogConfig) -> io::Result<Self> {
+        let mut metadata = Map::new();
+        if let Some(ref cluster) = config.cluster {
+            metadata.insert("_cluster".to_string(), Value::String(cluster.clone()));
+        }
+        for (key, value) in config.metadata() {
+            metadata.insert(format!("_{}", key), Value::String(value));
+        }
+        let mut connection = Connection::new(config)?;
+        let (sender, receiver) = sync_channel(QUEUE_SIZE);
+        let pending = Arc::new(AtomicUsize::new(0));
+        let pending_clone = pending.clone();
+        thread::spawn(move || connection.run(receiver, &pending_clone));
+        Ok(Self {
+            host: env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string()),
+            metadata,
+            sender: Mutex::new(sender),
+            pending,
+        })
+    }
+
+    fn message(&self, record: &Record) -> Value {
+        let text = record.args().to_string();
+        let mut message = self.metadata.clone();
+        // error dumps start with a line break
+        let short_message = text.trim().lines().next().unwrap_or_default().to_string();
+        if short_message.len() < text.len() {
+            message.insert("full_message".to_string(), Value::String(text.clone()));
+        }
+        message.insert("version".to_string(), json!("1.1"));
+        message.insert("host".to_string(), json!(self.host));
+        message.insert("short_message".to_string(), Value::String(short_message));
+        message.insert("timestamp".to_string(), json!(timestamp()));
+        message.insert("level".to_string(), json!(syslog_level(record.level())));
+        message.insert("_target".to_string(), json!(record.target()));
+        if let Some(module) = record.module_path() {
+            message.insert("_module".to_string(), json!(module));
+        }
+        if let (Some(file), Some(line)) = (record.file(), record.line()) {
+            message.insert("_file".to_string(), json!(file));
+            message.insert("_line".to_string(), json!(line));
+        }
+        // fields of the request being handled, e.g. its id
+        for (key, value) in context::fields() {
+            message.insert(format!("_{}", key), Value::String(value));
+        }
+        // access log lines are json, their fields are searchable on their own
+        if record.target() == "access_log" {
+            if let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(&text) {
+                for (key, value) in fields {
+                    if !value.is_null() {
+                        message.insert(format!("_{}", snake_case(&key)), value);
+                    }
+                }
+            }
+        }
+        Value::Object(message)
+    }
+}
+
+impl Log for GelfLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let data = match serde_json::to_vec(&self.message(record)) {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        if self.sender.lock().unwrap().try_send(data).is_err() {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Waits until queued messages are sent, e.g. before exit
+    fn flush(&self) {
+        let started = Instant::now();
+        while self.pending.load(Ordering::SeqCst) > 0 && started.elapsed() < FLUSH_TIMEOUT {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+enum Stream {
+    Udp(UdpSocket),
+    Tcp(Box<Write + Send>),
+}
+
+/// Owned by the sending thread, reconnects after errors
+struct Connection {
+    addr: String,
+    transport: GelfTransport,
+    tls: Option<(TlsConnector, String)>,
+    stream: Option<Stream>,
+}
+
+impl Connection {
+    fn new(config: &GrayLogConfig) -> io::Result<Self> {
+        let tls = if config.transport == GelfTransport::Tls {
+            let mut builder = TlsConnector::builder();
+            if let Some(ref path) = config.ca_path {
+                let cert = Certificate::from_pem(&fs::read(path)?).map_err(other)?;
+                builder.add_root_certificate(cert);
+            }
+            let domain = config
+                .tls_domain
+                .clone()
+                .unwrap_or_else(|| config.addr.rsplitn(2, ':').last().unwrap_or_default().to_string());
+            Some((builder.build().map_err(other)?, domain))
+        } else {
+            None
+        };
+        Ok(Self {
+            addr: config.addr.clone(),
+            transport: config.transport,
+            tls,
+            stream: None,
+        })
+    }
+
+    fn run(&mut self, receiver: Receiver<Vec<u8>>, pending: &AtomicUsize) {
+        for data in receiver {
+            let result = self.send(&data);
+            pending.fetch_sub(1, Ordering::SeqCst);
+            if let Err(e) = result {
+                eprintln!("Error sending message to graylog at {}: {}", self.addr, e);
+                self.stream = None;
+                thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.stream.is_none() {
+            self.stream = Some(self.connect()?);
+        }
+        match self.stream {
+            Some(Stream::Udp(ref socket)) => send_chunked(socket, data),
+            // messages are delimited by null byte on stream transports
+            Some(Stream::Tcp(ref mut stream)) => stream
+                .write_all(data)
+                .and_then(|_| stream.write_all(&[0]))
+                .and_then(|_| stream.flush()),
+            None => Ok(()),
+        }
+    }
+
+    fn connect(&self) -> io::Result<Stream> {
+        if self.transport == GelfTransport::Udp {
+            let target = self
+                .addr
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| other(format!("can't resolve {}", self.addr)))?;
+            let local = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+            let socket = UdpSocket::bind(local)?;
+            socket.connect(target)?;
+            return Ok(Stream::Udp(socket));
+        }
+        let stream = TcpStream::connect(&self.addr)?;
+        match self.tls {
+            Some((ref connector, ref domain)) => {
+                let stream = connector.connect(domain, stream).map_err(other)?;
+                Ok(Stream::Tcp(Box::new(stream)))
+            }
+            None => Ok(Stream::Tcp(Box::new(stream))),
+        }
+    }
+}
+
+/// Splits message over the size of one datagram into GELF chunks
+fn send_chunked(socket: &UdpSocket, data: &[u8]) -> io::Result<()> {
+    if data.len() <= UDP_CHUNK_SIZE {
+        return socket.send(data).map(|_| ());
+    }
+    let chunks: Vec<&[u8]> = data.chunks(UDP_CHUNK_SIZE - UDP_CHUNK_HEADER_SIZE).collect();
+    if chunks.len() > UDP_MAX_CHUNKS {
+        return Err(other(format!("message of {} bytes is too large for udp, use tcp", data.len())));
+    }
+    let uuid = Uuid::new_v4();
+    let id = &uuid.as_bytes()[..8];
+    for (seq, chunk) in chunks.iter().enumerate() {
+        let mut datagram = Vec::with_capacity(UDP_CHUNK_HEADER_SIZE + chunk.len());
+        datagram.extend_from_slice(&[0x1e, 0x0f]);
+        datagram.extend_from_slice(id);
+        datagram.push(seq as u8);
+        datagram.push(chunks.len() as u8);
+        datagram.extend_from_slice(chunk);
+        socket.send(&datagram)?;
+    }
+    Ok(())
+}
+
+fn syslog_level(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+fn timestamp() -> f64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_secs() as f64 + f64::from(since_epoch.subsec_millis()) / 1000.0
+}
+
+fn snake_case(key: &str) -> String {
+    key.chars().fold(String::new(), |mut result, c| {
+        if c.is_uppercase() {
+            result.push('_');
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+        result
+    })
+}
+
+fn other<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
@@ -0,0 +1,220 @@
+mod context;
+mod file;
+mod gelf;
+
+pub use self::context::with_fields;
+pub use self::file::Rotation;
+pub use self::gelf::GelfTransport;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+
+use chrono::prelude::*;
+use env_logger::Builder as EnvLogBuilder;
+use log::{self, LevelFilter as LogLevelFilter, Log, Metadata, Record};
+use simplelog::{Config as SimpleLoggerConfig, WriteLogger};
+
+use self::file::RotatingFile;
+use self::gelf::GelfLogger;
+use config::Config;
+
+lazy_static! {
+    static ref LEVELS: RwLock<LogLevels> = RwLock::new(LogLevels::default());
+}
+
+pub struct CombinedLogger {
+    pub inner: Vec<Arc<Log>>,
+    pub filter: Box<Fn(&Record) -> bool + Send + Sync>,
+}
+
+impl Default for CombinedLogger {
+    fn default() -> Self {
+        Self {
+            inner: vec![],
+            filter: Box::new(|_| true),
+        }
+    }
+}
+
+impl Log for CombinedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.iter().any(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if (self.filter)(record) {
+            for logger in &self.inner {
+                logger.log(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        for logger in &self.inner {
+            logger.flush();
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GrayLogConfig {
+    /// Endpoint to send messages to
+    pub addr: String,
+    pub cluster: Option<String>,
+    #[serde(default)]
+    pub transport: GelfTransport,
+    /// CA certificate of Graylog with tls transport, system roots if not set
+    pub ca_path: Option<String>,
+    /// Name expected in Graylog certificate, host of `addr` if not set
+    pub tls_domain: Option<String>,
+    /// Static fields added to every message, in addition to `source_type` and `stack` unless they are overridden
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl GrayLogConfig {
+    /// Configured metadata merged over the default one
+    pub fn metadata(&self) -> BTreeMap<String, String> {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("source_type".to_string(), "backend".to_string());
+        metadata.insert("stack".to_string(), "payments".to_string());
+        metadata.extend(self.metadata.clone());
+        metadata
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileLogConfig {
+    /// Path to file, appended to across restarts
+    pub path: String,
+    /// File is rotated when it would grow over this size, in bytes
+    pub max_size: Option<u64>,
+    #[serde(default)]
+    pub rotation: Rotation,
+    /// Number of rotated files kept, older ones are removed
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+/// Levels of log targets, a module's level applies to its submodules unless they have their own
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogLevels {
+    /// Level of targets not matched by `modules`
+    pub level: LogLevelFilter,
+    #[serde(default)]
+    pub modules: BTreeMap<String, LogLevelFilter>,
+}
+
+impl Default for LogLevels {
+    fn default() -> Self {
+        Self {
+            level: LogLevelFilter::Info,
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+impl LogLevels {
+    /// Parses `RUST_LOG` style spec, e.g. `warn,bitcoin_proxy::healthcheck=debug`
+    pub fn parse(spec: &str) -> Self {
+        let mut levels = LogLevels::default();
+        // regex filter after `/` is not supported
+        let spec = spec.split('/').next().unwrap_or_default();
+        for directive in spec
+            .split(',')
+            .map(|directive| directive.trim())
+            .filter(|directive| !directive.is_empty())
+        {
+            let mut parts = directive.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(level), None) => match level.parse() {
+                    Ok(level) => levels.level = level,
+                    // bare module name enables all its levels
+                    Err(_) => {
+                        levels.modules.insert(level.to_string(), LogLevelFilter::Trace);
+                    }
+                },
+                (Some(module), Some(level)) => match level.parse() {
+                    Ok(level) => {
+                        levels.modules.insert(module.to_string(), level);
+                    }
+                    Err(_) => eprintln!("Ignoring invalid log level in directive {}", directive),
+                },
+                _ => (),
+            }
+        }
+        levels
+    }
+
+    fn filter(&self, target: &str) -> LogLevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| target == module.as_str() || target.starts_with(&format!("{}::", module)))
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    fn max(&self) -> LogLevelFilter {
+        self.modules.values().cloned().fold(self.level, ::std::cmp::max)
+    }
+}
+
+/// Current levels of log targets
+pub fn levels() -> LogLevels {
+    LEVELS.read().unwrap().clone()
+}
+
+/// Changes levels of log targets at runtime
+pub fn set_levels(levels: LogLevels) {
+    log::set_max_level(levels.max());
+    info!("Log levels set to {:?}", levels);
+    *LEVELS.write().unwrap() = levels;
+}
+
+fn enabled(metadata: &Metadata) -> bool {
+    metadata.level() <= LEVELS.read().unwrap().filter(metadata.target())
+}
+
+/// Installs logger with levels from `RUST_LOG` if it's set, from `logging` config otherwise
+pub fn init(config: &Config) {
+    let mut builder = EnvLogBuilder::new();
+    builder
+        .format(|formatter, record| {
+            let now = Utc::now();
+            writeln!(formatter, "{} - {:5} - {}", now.to_rfc3339(), record.level(), record.args())
+        })
+        // records are filtered by levels that can be changed at runtime
+        .filter(None, LogLevelFilter::Trace);
+
+    let levels = match env::var("RUST_LOG") {
+        Ok(spec) => LogLevels::parse(&spec),
+        Err(_) => config.logging.clone().unwrap_or_default(),
+    };
+
+    let mut combined_logger = CombinedLogger::default();
+    combined_logger.filter = Box::new(|record: &Record| enabled(record.metadata()));
+
+    if let Some(ref config) = config.filelog {
+        let file = RotatingFile::open(&config.path, config.max_size, config.rotation, config.max_files)
+            .unwrap_or_else(|e| panic!("Error opening log file {}: {}", config.path, e));
+        let logger = WriteLogger::new(LogLevelFilter::Trace, SimpleLoggerConfig::default(), file);
+        combined_logger.inner.push(Arc::new(*logger));
+    } else {
+        combined_logger.inner.push(Arc::new(builder.build()));
+    }
+
+    if let Some(ref config) = config.graylog {
+        let logger = GelfLogger::new(config).unwrap_or_else(|e| panic!("Error creating graylog logger: {}", e));
+        combined_logger.inner.push(Arc::new(logger));
+    }
+
+    log::set_boxed_logger(Box::new(combined_logger)).expect("Failed to install logger");
+    set_levels(levels);
+}
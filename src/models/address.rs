@@ -0,0 +1,32 @@
+/// Unspent output locked to an address
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Utxo {
+    pub txid: String,
+    pub vout: u32,
+    pub value: u64,
+    pub height: u64,
+}
+
+/// Balance of an address in satoshis, as seen by the index. It's partial when the index
+/// doesn't start from genesis: funds received before `indexed_from` are not counted.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressBalance {
+    pub address: String,
+    pub balance: u64,
+    pub received: u64,
+    pub sent: u64,
+    pub utxo_count: usize,
+    pub indexed_height: Option<u64>,
+    /// Height of the first indexed block
+    pub indexed_from: Option<u64>,
+}
+
+/// Transaction that either funded or spent from an address
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressTransaction {
+    pub txid: String,
+    pub height: u64,
+}
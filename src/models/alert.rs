@@ -0,0 +1,79 @@
+/// Condition detected by healthcheck that someone should know about
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub severity: AlertSeverity,
+    pub message: String,
+    /// Node the alert is about or, for unavailable reference, network other than default
+    pub node: Option<String>,
+}
+
+impl Alert {
+    pub fn new(kind: AlertKind, severity: AlertSeverity, message: String, node: Option<String>) -> Self {
+        Self {
+            kind,
+            severity,
+            message,
+            node,
+        }
+    }
+
+    /// Stable key of the condition, e.g. `node-lag:<url>`, used to de-duplicate and close alerts
+    pub fn alias(&self) -> String {
+        alias(self.kind, self.node.as_ref().map(|node| node.as_str()))
+    }
+}
+
+pub fn alias(kind: AlertKind, node: Option<&str>) -> String {
+    let kind = match kind {
+        AlertKind::NodeLag => "node-lag",
+        AlertKind::NodeUnreachable => "node-unreachable",
+        AlertKind::ReferenceUnavailable => "reference-unavailable",
+    };
+    match node {
+        Some(node) => format!("{}:{}", kind, node),
+        None => kind.to_string(),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// Node is behind the reference height
+    NodeLag,
+    /// Node doesn't respond to rpc calls
+    NodeUnreachable,
+    /// Reference height provider is not available
+    ReferenceUnavailable,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Default for AlertSeverity {
+    fn default() -> Self {
+        AlertSeverity::Info
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+/// Alert state change sent to webhook sinks
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertEvent {
+    pub status: AlertStatus,
+    pub alias: String,
+    pub alert: Alert,
+}
@@ -1,23 +1,56 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use chrono::NaiveDateTime;
 
+/// Number of quarantine, probation and recovery events kept per node
+const MAX_HISTORY: usize = 100;
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct BitcoinNode {
     pub url: String,
     pub user: String,
+    #[serde(skip_serializing, default)]
     pub password: String,
+    /// Bitcoind `.cookie` file, used instead of user and password if set
+    pub cookie_file: Option<String>,
+    pub zmq_url: Option<String>,
     pub quarantine: Quarantine,
     pub main: bool,
+    /// Consecutive healthchecks the node failed
+    pub failures: u32,
+    /// Height of node's best block on the last healthcheck
+    pub last_height: Option<u64>,
+    /// Number of quarantines in a row, each one lasts twice as long as the previous one
+    pub quarantine_level: u32,
+    /// End of the last quarantine
+    pub last_quarantine_end: Option<NaiveDateTime>,
+    pub history: Vec<NodeEvent>,
 }
 
 impl BitcoinNode {
-    pub fn new(url: String, user: String, password: String) -> Self {
+    pub fn new(url: String, user: String, password: String, cookie_file: Option<String>, zmq_url: Option<String>) -> Self {
         Self {
             url,
             user,
             password,
+            cookie_file,
+            zmq_url,
             quarantine: Quarantine::No,
             main: false,
+            failures: 0,
+            last_height: None,
+            quarantine_level: 0,
+            last_quarantine_end: None,
+            history: vec![],
+        }
+    }
+
+    pub fn record(&mut self, at: NaiveDateTime, kind: NodeEventKind) {
+        self.history.push(NodeEvent { at, kind });
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
         }
     }
 }
@@ -25,5 +58,76 @@ impl BitcoinNode {
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub enum Quarantine {
     No,
+    /// Quarantined until the time
     Yes(NaiveDateTime),
+    /// Released from quarantine, with number of healthchecks passed since
+    Probation(u32),
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeEvent {
+    pub at: NaiveDateTime,
+    #[serde(flatten)]
+    pub kind: NodeEventKind,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum NodeEventKind {
+    #[serde(rename_all = "camelCase")]
+    Quarantined {
+        until: NaiveDateTime,
+        level: u32,
+        reason: String,
+    },
+    Probation,
+    Recovered,
+}
+
+/// Returns main node that is not in quarantine. If there is none, the first node not in quarantine
+/// or, if all nodes are in quarantine, the first one is promoted to main and returned.
+pub fn active_node(nodes: &mut BTreeMap<usize, BitcoinNode>) -> BitcoinNode {
+    if let Some(node) = nodes.values().filter(|n| n.main && n.quarantine == Quarantine::No).nth(0) {
+        return node.clone();
+    }
+    let i = nodes
+        .iter()
+        .filter(|(_, n)| n.quarantine == Quarantine::No)
+        .map(|(i, _)| *i)
+        .nth(0)
+        .unwrap_or(0);
+    for (j, n) in nodes.iter_mut() {
+        n.main = *j == i;
+    }
+    nodes.get(&i).cloned().expect("There is no nodes defined in config")
+}
+
+/// Returns node for read-only proxied request: `traffic_percent` of them go to nodes on probation, the rest to the active node
+pub fn proxy_node(nodes: &mut BTreeMap<usize, BitcoinNode>, traffic_percent: u64, counter: &AtomicUsize) -> BitcoinNode {
+    let probation = probation_nodes(nodes);
+    if !probation.is_empty() {
+        let n = counter.fetch_add(1, Ordering::Relaxed) as u64;
+        if n % 100 < traffic_percent {
+            return probation[(n / 100) as usize % probation.len()].clone();
+        }
+    }
+    active_node(nodes)
+}
+
+/// Returns all nodes that are not in quarantine
+pub fn healthy_nodes(nodes: &BTreeMap<usize, BitcoinNode>) -> Vec<BitcoinNode> {
+    nodes.values().filter(|n| n.quarantine == Quarantine::No).cloned().collect()
+}
+
+/// Returns nodes released from quarantine that didn't pass enough healthchecks yet
+pub fn probation_nodes(nodes: &BTreeMap<usize, BitcoinNode>) -> Vec<BitcoinNode> {
+    nodes
+        .values()
+        .filter(|n| match n.quarantine {
+            Quarantine::Probation(_) => true,
+            _ => false,
+        })
+        .cloned()
+        .collect()
 }
@@ -0,0 +1,30 @@
+/// Outcome of broadcasting a transaction to all healthy nodes and push APIs
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastReport {
+    /// Set if at least one target accepted the transaction
+    pub txid: Option<String>,
+    /// Most common rejection reason, set if no target accepted the transaction
+    pub rejection: Option<BroadcastRejection>,
+    pub results: Vec<BroadcastResult>,
+    /// Urls of nodes the transaction was sent to
+    #[serde(skip)]
+    pub nodes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastResult {
+    /// Node or push API url
+    pub target: String,
+    pub accepted: bool,
+    pub txid: Option<String>,
+    pub rejection: Option<BroadcastRejection>,
+}
+
+/// Json rpc error as returned by bitcoind
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct BroadcastRejection {
+    pub code: i64,
+    pub message: String,
+}
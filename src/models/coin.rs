@@ -0,0 +1,37 @@
+/// Bitcoind-compatible chains that can be proxied, their daemons expose the same json rpc
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Coin {
+    Bitcoin,
+    Litecoin,
+    BitcoinCash,
+    Dogecoin,
+}
+
+impl Default for Coin {
+    fn default() -> Self {
+        Coin::Bitcoin
+    }
+}
+
+impl Coin {
+    /// Target time between blocks, in seconds
+    pub fn block_time(&self) -> u64 {
+        match self {
+            Coin::Bitcoin | Coin::BitcoinCash => 600,
+            Coin::Litecoin => 150,
+            Coin::Dogecoin => 60,
+        }
+    }
+
+    /// Public source of mainnet reference height as url and json pointer to height in its response.
+    /// `None` for bitcoin, which uses `healthcheck.url`.
+    pub fn reference(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Coin::Bitcoin => None,
+            Coin::Litecoin => Some(("https://api.blockcypher.com/v1/ltc/main", "/height")),
+            Coin::BitcoinCash => Some(("https://api.blockchair.com/bitcoin-cash/stats", "/data/best_block_height")),
+            Coin::Dogecoin => Some(("https://api.blockcypher.com/v1/doge/main", "/height")),
+        }
+    }
+}
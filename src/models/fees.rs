@@ -0,0 +1,25 @@
+/// Aggregated fee recommendations, valid for the block they were computed at
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeEstimates {
+    pub height: u64,
+    pub estimates: Vec<FeeEstimate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeEstimate {
+    /// Confirmation target in blocks
+    pub target: u16,
+    /// Recommended fee rate in sat/vB
+    pub fee_rate: u64,
+    pub sources: Vec<FeeSource>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeSource {
+    pub source: String,
+    /// Fee rate in sat/vB reported by the source
+    pub fee_rate: f64,
+}
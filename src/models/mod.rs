@@ -1,3 +1,21 @@
+mod address;
+mod alert;
 mod bitcoin_node;
+mod broadcast;
+mod coin;
+mod fees;
+mod notification;
+mod tracked_transaction;
+mod watch;
+mod webhook;
 
+pub use self::address::*;
+pub use self::alert::*;
 pub use self::bitcoin_node::*;
+pub use self::broadcast::*;
+pub use self::coin::*;
+pub use self::fees::*;
+pub use self::notification::*;
+pub use self::tracked_transaction::*;
+pub use self::watch::*;
+pub use self::webhook::*;
@@ -0,0 +1,19 @@
+/// Event relayed from bitcoind ZMQ publisher or produced by a watch
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    /// ZMQ topic, e.g. `hashblock` or `rawtx`, or `watch:<id>` for watch events
+    pub topic: String,
+    /// Hex encoded message body for ZMQ topics, json encoded `WatchEvent` for watch topics
+    pub data: String,
+    /// Node the notification was received from
+    pub node: String,
+}
+
+/// Message sent by websocket clients to manage their topics
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+#[serde(default)]
+pub struct SubscriptionRequest {
+    pub subscribe: Vec<String>,
+    pub unsubscribe: Vec<String>,
+}
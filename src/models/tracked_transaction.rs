@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+
+use super::BroadcastRejection;
+
+/// Transaction submitted through the proxy and watched until it's buried deep enough
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackedTransaction {
+    pub txid: String,
+    #[serde(skip_serializing, default)]
+    pub hex: String,
+    pub status: TransactionStatus,
+    pub block: Option<TransactionBlock>,
+    pub confirmations: u64,
+    /// Nodes that had the transaction in mempool on the last check
+    pub in_mempool_of: Vec<String>,
+    pub rebroadcasts: u32,
+    /// Rebroadcast attempts by node url
+    #[serde(default)]
+    pub rebroadcasts_to: BTreeMap<String, NodeRebroadcast>,
+    pub last_rejection: Option<BroadcastRejection>,
+    pub submitted_at: NaiveDateTime,
+    pub checked_at: Option<NaiveDateTime>,
+}
+
+impl TrackedTransaction {
+    pub fn new(txid: String, hex: String, submitted_at: NaiveDateTime) -> Self {
+        Self {
+            txid,
+            hex,
+            status: TransactionStatus::Pending,
+            block: None,
+            confirmations: 0,
+            in_mempool_of: vec![],
+            rebroadcasts: 0,
+            rebroadcasts_to: BTreeMap::new(),
+            last_rejection: None,
+            submitted_at,
+            checked_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeRebroadcast {
+    pub attempts: u32,
+    pub last_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    /// In mempool of at least one node
+    Pending,
+    /// Included in the best chain
+    Confirmed,
+    /// Dropped from all mempools and rejected on rebroadcast
+    Evicted,
+    /// Inputs are spent by another transaction
+    Conflicted,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionBlock {
+    pub height: u64,
+    pub hash: String,
+}
@@ -0,0 +1,98 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use super::TransactionBlock;
+
+/// Request to watch an address for incoming funds or a transaction for confirmations
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NewWatch {
+    pub address: Option<String>,
+    pub txid: Option<String>,
+    /// Number of confirmations to report, each one is a separate event
+    pub confirmations: u64,
+    /// Url to post events to, if missing events are published to websocket topic `watch:<id>`.
+    /// Hosts not listed in `callback_hosts` require admin token.
+    pub callback_url: Option<String>,
+    /// Key for HMAC-SHA256 signature of callbacks
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Watch {
+    pub id: Uuid,
+    pub address: Option<String>,
+    pub txid: Option<String>,
+    pub confirmations: u64,
+    pub callback_url: Option<String>,
+    #[serde(skip_serializing)]
+    pub secret: Option<String>,
+    pub created_at: NaiveDateTime,
+    /// Transactions matching the watch seen so far
+    pub transactions: Vec<WatchedTransaction>,
+}
+
+impl Watch {
+    pub fn new(new_watch: NewWatch, created_at: NaiveDateTime) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            address: new_watch.address,
+            txid: new_watch.txid,
+            confirmations: new_watch.confirmations,
+            callback_url: new_watch.callback_url,
+            secret: new_watch.secret,
+            created_at,
+            transactions: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedTransaction {
+    pub txid: String,
+    /// Satoshis received by the watched address
+    pub value: Option<u64>,
+    pub block: Option<TransactionBlock>,
+    /// Confirmations reported so far
+    pub confirmations: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchEventKind {
+    /// Matching transaction appeared in mempool or block for the first time
+    Seen,
+    /// Matching transaction reached `confirmations`
+    Confirmed,
+    /// Block with matching transaction was reorged out
+    Unconfirmed,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchEvent {
+    pub watch_id: Uuid,
+    #[serde(rename = "type")]
+    pub kind: WatchEventKind,
+    pub address: Option<String>,
+    pub txid: String,
+    pub value: Option<u64>,
+    pub block: Option<TransactionBlock>,
+    pub confirmations: u64,
+}
+
+impl WatchEvent {
+    pub fn new(watch: &Watch, kind: WatchEventKind, tx: &WatchedTransaction) -> Self {
+        Self {
+            watch_id: watch.id,
+            kind,
+            address: watch.address.clone(),
+            txid: tx.txid.clone(),
+            value: tx.value,
+            block: tx.block.clone(),
+            confirmations: tx.confirmations,
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Body of a webhook request. `id` is stable across delivery retries, so subscribers can de-duplicate.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload<T: Serialize> {
+    pub id: Uuid,
+    pub created_at: NaiveDateTime,
+    #[serde(flatten)]
+    pub event: T,
+}
+
+impl<T: Serialize> WebhookPayload<T> {
+    pub fn new(event: T) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            created_at: ::chrono::Utc::now().naive_utc(),
+            event,
+        }
+    }
+}
+
+/// Change of the best chain
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ChainEvent {
+    /// New block connected to the best chain
+    #[serde(rename_all = "camelCase")]
+    Block { height: u64, hash: String },
+    /// Blocks above `fork_height` were disconnected, newest first
+    #[serde(rename_all = "camelCase")]
+    Reorg { fork_height: u64, disconnected: Vec<String> },
+}
@@ -0,0 +1,104 @@
+//! Additional node pools, e.g. testnet next to mainnet or other bitcoind-compatible coins,
+//! each served under its own path prefix or hosts.
+
+mod error;
+
+pub use self::error::*;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{Arc, Mutex};
+
+use failure::Fail;
+use futures::future;
+use futures::prelude::*;
+use tokio_core;
+
+use client::{BitcoinClient, BitcoinClientImpl, HttpClient};
+use config::{Config, Network as NetworkConfig};
+use models::*;
+use utils::log_warn;
+
+/// Nodes of an additional network
+#[derive(Clone)]
+pub struct Network {
+    pub config: NetworkConfig,
+    pub nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    /// Counter of proxied requests, used to send a share of them to nodes on probation
+    pub requests_counter: Arc<AtomicUsize>,
+}
+
+#[derive(Clone, Default)]
+pub struct Networks {
+    networks: Arc<Vec<Network>>,
+}
+
+impl Networks {
+    pub fn new(config: &Config) -> Self {
+        let networks = config
+            .networks
+            .iter()
+            .map(|network| Network {
+                config: network.clone(),
+                nodes: Arc::new(Mutex::new(network.to_nodes())),
+                requests_counter: Arc::new(AtomicUsize::new(0)),
+            })
+            .collect();
+        Self {
+            networks: Arc::new(networks),
+        }
+    }
+
+    pub fn all(&self) -> &[Network] {
+        &self.networks
+    }
+
+    /// Finds network by name, i.e. by the first segment of request path
+    pub fn by_name(&self, name: &str) -> Option<&Network> {
+        self.networks.iter().find(|network| network.config.name == name)
+    }
+
+    /// Finds network by `Host` header, port is ignored
+    pub fn by_host(&self, host: &str) -> Option<&Network> {
+        let host = host.split(':').next().unwrap_or_default();
+        self.networks
+            .iter()
+            .find(|network| network.config.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+    }
+}
+
+/// Checks that nodes report the chain configured for their network, so that e.g. testnet node
+/// never serves mainnet requests. Unreachable nodes are skipped, healthcheck takes care of them.
+pub fn verify<C: HttpClient>(config: &Config, nodes: &BTreeMap<usize, BitcoinNode>, networks: &Networks, client: C) -> Result<(), Error> {
+    let client: Arc<HttpClient> = Arc::new(client);
+    let mut checks = Vec::new();
+    if let Some(ref chain) = config.chain {
+        checks.extend(nodes.values().map(|node| verify_node(client.clone(), chain.clone(), node.clone())));
+    }
+    for network in networks.all() {
+        let nodes = network.nodes.lock().unwrap();
+        checks.extend(
+            nodes
+                .values()
+                .map(|node| verify_node(client.clone(), network.config.chain.clone(), node.clone())),
+        );
+    }
+    let mut core = tokio_core::reactor::Core::new().map_err(ectx!(try ErrorSource::Io, ErrorKind::Internal))?;
+    core.run(future::join_all(checks)).map(|_| ())
+}
+
+fn verify_node(client: Arc<HttpClient>, chain: String, node: BitcoinNode) -> impl Future<Item = (), Error = Error> {
+    let bitcoin_client = BitcoinClientImpl::from_node(client, &node);
+    bitcoin_client.get_blockchain_info().then(move |r| match r {
+        Ok(ref info) if info.chain == chain => {
+            info!("Node {} is on {} chain", node.url, chain);
+            Ok(())
+        }
+        Ok(info) => Err(ectx!(err ErrorContext::Verify, ErrorKind::WrongChain => node.url, chain, info.chain)),
+        Err(e) => {
+            log_warn(&e);
+            warn!("Couldn't verify chain of node {}", node.url);
+            Ok(())
+        }
+    })
+}
@@ -0,0 +1,30 @@
+use failure::{Backtrace, Context, Fail};
+use std::fmt;
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub struct Error {
+    inner: Context<ErrorKind>,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorKind {
+    #[fail(display = "notifications error - internal error")]
+    Internal,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
+pub enum ErrorSource {
+    #[fail(display = "notifications source - error inside of ZMQ library")]
+    Zmq,
+    #[fail(display = "notifications source - error inside of websocket library")]
+    WebSocket,
+    #[fail(display = "notifications source - io error")]
+    Io,
+    #[fail(display = "notifications source - error serializing json")]
+    Json,
+}
+
+derive_error_impls!();
@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+use models::*;
+
+struct Subscriber {
+    id: usize,
+    topics: HashSet<String>,
+    sender: SyncSender<Notification>,
+}
+
+/// Fans out notifications to subscribers according to their topics
+#[derive(Clone, Default)]
+pub struct NotificationHub {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    next_id: Arc<AtomicUsize>,
+}
+
+impl NotificationHub {
+    /// Registers subscriber without topics, returns its id
+    pub fn subscribe(&self, sender: SyncSender<Notification>) -> usize {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.subscribers.lock().unwrap().push(Subscriber {
+            id,
+            topics: HashSet::new(),
+            sender,
+        });
+        id
+    }
+
+    pub fn unsubscribe(&self, id: usize) {
+        self.subscribers.lock().unwrap().retain(|s| s.id != id);
+    }
+
+    pub fn update_topics(&self, id: usize, request: &SubscriptionRequest) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(subscriber) = subscribers.iter_mut().find(|s| s.id == id) {
+            subscriber.topics.extend(request.subscribe.iter().cloned());
+            for topic in &request.unsubscribe {
+                subscriber.topics.remove(topic);
+            }
+        }
+    }
+
+    /// Sends notification to everyone subscribed to its topic, dropping disconnected subscribers
+    /// and ones too slow to keep up with their queue
+    pub fn publish(&self, notification: Notification) {
+        self.subscribers.lock().unwrap().retain(|s| {
+            if !s.topics.contains(&notification.topic) {
+                return true;
+            }
+            match s.sender.try_send(notification.clone()) {
+                Ok(_) => true,
+                Err(TrySendError::Full(_)) => {
+                    warn!("Websocket subscriber {} is too slow, disconnecting", s.id);
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+}
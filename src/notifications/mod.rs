@@ -0,0 +1,22 @@
+//! Relay of bitcoind ZMQ notifications to websocket subscribers.
+//!
+//! Clients connect to the websocket server and send `{"subscribe": ["hashblock"]}`
+//! (or `unsubscribe`) messages, receiving `{"topic", "data", "node"}` json for each event.
+
+mod error;
+mod hub;
+mod relay;
+mod websocket;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+pub use self::hub::*;
+use config::Notifications as NotificationsConfig;
+use models::*;
+
+/// Starts ZMQ relay and websocket server threads
+pub fn start(config: NotificationsConfig, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>, hub: NotificationHub) {
+    relay::start(config.clone(), nodes, hub.clone());
+    websocket::start(config, hub);
+}
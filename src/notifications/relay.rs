@@ -0,0 +1,141 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use failure::Fail;
+use hex;
+use zmq;
+
+use super::error::*;
+use super::hub::NotificationHub;
+use config::Notifications as NotificationsConfig;
+use models::*;
+use utils::log_error;
+
+/// How long to wait for a message before checking if active node changed
+const RECEIVE_TIMEOUT_MS: i32 = 1000;
+/// Pause before reconnecting after error or when no node has ZMQ configured
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// Number of recent notifications remembered for de-duplication
+const RECENT_CAPACITY: usize = 10000;
+
+/// Remembers recently relayed notifications, so that the same event coming
+/// from different nodes after failover is relayed only once
+struct RecentNotifications {
+    order: VecDeque<(String, u64)>,
+    seen: HashSet<(String, u64)>,
+}
+
+impl RecentNotifications {
+    fn new() -> Self {
+        Self {
+            order: VecDeque::with_capacity(RECENT_CAPACITY),
+            seen: HashSet::with_capacity(RECENT_CAPACITY),
+        }
+    }
+
+    /// Returns `false` if notification was already seen
+    fn insert(&mut self, topic: &str, body: &[u8]) -> bool {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let key = (topic.to_string(), hasher.finish());
+        if self.seen.contains(&key) {
+            return false;
+        }
+        if self.order.len() == RECENT_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.seen.insert(key);
+        true
+    }
+}
+
+/// Spawns a thread that subscribes to ZMQ endpoint of the active node and publishes its notifications to hub
+pub fn start(config: NotificationsConfig, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>, hub: NotificationHub) {
+    thread::spawn(move || {
+        let context = zmq::Context::new();
+        let mut recent = RecentNotifications::new();
+        loop {
+            let (url, zmq_url) = match zmq_node(&nodes) {
+                Some(node) => node,
+                None => {
+                    warn!("No healthy node with ZMQ endpoint configured");
+                    thread::sleep(RETRY_INTERVAL);
+                    continue;
+                }
+            };
+            info!("Subscribing to ZMQ notifications of node {} at {}", url, zmq_url);
+            if let Err(e) = relay(&context, &config, &url, &zmq_url, &nodes, &hub, &mut recent) {
+                log_error(&e);
+                thread::sleep(RETRY_INTERVAL);
+            }
+        }
+    });
+}
+
+/// Relays notifications from `zmq_url` until another node becomes active
+fn relay(
+    context: &zmq::Context,
+    config: &NotificationsConfig,
+    url: &str,
+    zmq_url: &str,
+    nodes: &Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    hub: &NotificationHub,
+    recent: &mut RecentNotifications,
+) -> Result<(), Error> {
+    let socket = context.socket(zmq::SUB).map_err(ectx!(try ErrorSource::Zmq, ErrorKind::Internal))?;
+    socket
+        .set_rcvtimeo(RECEIVE_TIMEOUT_MS)
+        .and_then(|_| socket.set_linger(0))
+        .and_then(|_| socket.connect(zmq_url))
+        .map_err(ectx!(try ErrorSource::Zmq, ErrorKind::Internal => zmq_url))?;
+    for topic in &config.topics {
+        socket
+            .set_subscribe(topic.as_bytes())
+            .map_err(ectx!(try ErrorSource::Zmq, ErrorKind::Internal => zmq_url, topic))?;
+    }
+    loop {
+        match socket.recv_multipart(0) {
+            // bitcoind sends topic, body and sequence number
+            Ok(frames) => {
+                if let (Some(topic), Some(body)) = (frames.get(0), frames.get(1)) {
+                    let topic = String::from_utf8_lossy(topic).to_string();
+                    if recent.insert(&topic, body) {
+                        hub.publish(Notification {
+                            topic,
+                            data: hex::encode(body),
+                            node: url.to_string(),
+                        });
+                    }
+                }
+            }
+            Err(zmq::Error::EAGAIN) => (),
+            Err(e) => return Err(ectx!(err e, ErrorSource::Zmq, ErrorKind::Internal => zmq_url)),
+        }
+        if zmq_node(nodes).map(|(_, active_zmq_url)| active_zmq_url != zmq_url).unwrap_or(true) {
+            info!("Active node changed, unsubscribing from ZMQ notifications at {}", zmq_url);
+            return Ok(());
+        }
+    }
+}
+
+/// Returns url and ZMQ endpoint of the active node or, if it has no ZMQ endpoint, of the first healthy node that has one
+fn zmq_node(nodes: &Arc<Mutex<BTreeMap<usize, BitcoinNode>>>) -> Option<(String, String)> {
+    let mut nodes = nodes.lock().unwrap();
+    let active = active_node(&mut nodes);
+    let node = if active.zmq_url.is_some() {
+        Some(active)
+    } else {
+        healthy_nodes(&nodes).into_iter().find(|node| node.zmq_url.is_some())
+    };
+    node.and_then(|node| {
+        let url = node.url;
+        node.zmq_url.map(|zmq_url| (url, zmq_url))
+    })
+}
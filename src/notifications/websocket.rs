@@ -0,0 +1,110 @@
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use failure::Fail;
+use serde_json;
+use tungstenite::{self, Message, WebSocket};
+
+use super::error::*;
+use super::hub::NotificationHub;
+use config::Notifications as NotificationsConfig;
+use models::*;
+use utils::{log_error, log_warn};
+
+/// How long to wait for client messages before flushing pending notifications
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Spawns a thread accepting websocket subscribers, each connection is served in its own thread
+pub fn start(config: NotificationsConfig, hub: NotificationHub) {
+    let connections = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        let addr = format!("{}:{}", config.host, config.port);
+        let listener = match TcpListener::bind(&addr) {
+            Ok(listener) => listener,
+            Err(e) => {
+                let e: Error = ectx!(err e, ErrorSource::Io, ErrorKind::Internal => addr);
+                log_error(&e);
+                return;
+            }
+        };
+        info!("Listening for websocket subscribers on ws://{}", addr);
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if connections.fetch_add(1, Ordering::SeqCst) >= config.max_connections {
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                        warn!("Refusing websocket subscriber, {} are already connected", config.max_connections);
+                        continue;
+                    }
+                    let hub = hub.clone();
+                    let connections = connections.clone();
+                    let queue_size = config.queue_size;
+                    thread::spawn(move || {
+                        if let Err(e) = serve(stream, hub, queue_size) {
+                            log_warn(&e);
+                        }
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                    });
+                }
+                Err(e) => log_warn(&e),
+            }
+        }
+    });
+}
+
+fn serve(stream: TcpStream, hub: NotificationHub, queue_size: usize) -> Result<(), Error> {
+    let peer_addr = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    let peer = &peer_addr;
+    let mut socket =
+        tungstenite::accept(stream).map_err(|e| ectx!(try err ErrorSource::WebSocket, ErrorKind::Internal => peer, e.to_string()))?;
+    socket
+        .get_mut()
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .map_err(ectx!(try ErrorSource::Io, ErrorKind::Internal => peer))?;
+    debug!("Websocket subscriber {} connected", peer);
+    let (sender, receiver) = mpsc::sync_channel(queue_size);
+    let id = hub.subscribe(sender);
+    let result = pump(&mut socket, &receiver, &hub, id);
+    hub.unsubscribe(id);
+    debug!("Websocket subscriber {} disconnected", peer);
+    result
+}
+
+/// Handles subscription requests and writes notifications until client disconnects or hub drops it
+fn pump(socket: &mut WebSocket<TcpStream>, receiver: &Receiver<Notification>, hub: &NotificationHub, id: usize) -> Result<(), Error> {
+    loop {
+        match socket.read_message() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<SubscriptionRequest>(&text) {
+                Ok(request) => hub.update_topics(id, &request),
+                Err(e) => {
+                    let e: Error = ectx!(err e, ErrorSource::Json, ErrorKind::Internal => text);
+                    log_warn(&e);
+                }
+            },
+            Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => return Ok(()),
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => (),
+            Err(e) => return Err(ectx!(err ErrorSource::WebSocket, ErrorKind::Internal => e.to_string())),
+        }
+        loop {
+            let notification = match receiver.try_recv() {
+                Ok(notification) => notification,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    let _ = socket.close(None);
+                    let _ = socket.write_pending();
+                    return Ok(());
+                }
+            };
+            let text = serde_json::to_string(&notification).map_err(ectx!(try ErrorSource::Json, ErrorKind::Internal))?;
+            socket
+                .write_message(Message::Text(text))
+                .map_err(|e| ectx!(try err ErrorSource::WebSocket, ErrorKind::Internal => e.to_string()))?;
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Masking of credentials and wallet secrets before requests, responses and errors are logged or reported.
+
+use hyper::header::{HeaderValue, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE};
+use hyper::{HeaderMap, Uri};
+use regex::Regex;
+use serde_json::{self, Value};
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Rpc methods with passphrases, private keys or seeds in params
+const SECRET_PARAMS_METHODS: &[&str] = &[
+    "createwallet",
+    "encryptwallet",
+    "importdescriptors",
+    "importmulti",
+    "importprivkey",
+    "sethdseed",
+    "signmessagewithprivkey",
+    "signrawtransaction",
+    "signrawtransactionwithkey",
+    "walletpassphrase",
+    "walletpassphrasechange",
+];
+
+/// Rpc methods returning private keys
+const SECRET_RESULT_METHODS: &[&str] = &["dumpprivkey", "listdescriptors"];
+
+/// Json keys holding secrets in bodies of other services, e.g. PagerDuty routing key
+const SECRET_KEYS: &[&str] = &["api_key", "password", "routing_key", "secret", "token"];
+
+/// Path segments at least this long are taken for tokens, e.g. secret part of Slack webhook url
+const MIN_TOKEN_LEN: usize = 20;
+
+lazy_static! {
+    static ref CREDENTIALS_REGEX: Regex = Regex::new(r"(?i)\b(basic|bearer|geniekey)\s+[A-Za-z0-9+/=._~-]+").unwrap();
+}
+
+/// Copy of headers with credentials masked
+pub fn headers(headers: &HeaderMap<HeaderValue>) -> HeaderMap<HeaderValue> {
+    let mut headers = headers.clone();
+    for name in &[AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE] {
+        if headers.contains_key(name) {
+            headers.insert(name.clone(), HeaderValue::from_static(REDACTED));
+        }
+    }
+    headers
+}
+
+/// Json rpc request, single or batch, with params of sensitive methods masked
+pub fn rpc_request(value: &Value) -> Value {
+    match value {
+        Value::Array(requests) => Value::Array(requests.iter().map(rpc_request).collect()),
+        Value::Object(request) => {
+            let mut request = request.clone();
+            if has_secret_params(value) && request.contains_key("params") {
+                request.insert("params".to_string(), Value::String(REDACTED.to_string()));
+            }
+            Value::Object(request)
+        }
+        other => other.clone(),
+    }
+}
+
+/// Url for logs and errors with credentials, query values and token-like path segments masked
+pub fn url(url: &str) -> String {
+    let uri = match url.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(_) => return REDACTED.to_string(),
+    };
+    let mut result = String::new();
+    if let (Some(scheme), Some(authority)) = (uri.scheme_part(), uri.authority_part()) {
+        let authority = authority.as_str();
+        let host = authority.rsplitn(2, '@').next().unwrap_or_default();
+        let userinfo = if host.len() < authority.len() {
+            format!("{}@", REDACTED)
+        } else {
+            String::new()
+        };
+        result.push_str(&format!("{}://{}{}", scheme, userinfo, host));
+    }
+    let path: Vec<&str> = uri
+        .path()
+        .split('/')
+        .map(|segment| if is_token(segment) { REDACTED } else { segment })
+        .collect();
+    result.push_str(&path.join("/"));
+    if let Some(query) = uri.query() {
+        let pairs: Vec<String> = query
+            .split('&')
+            .map(|pair| match pair.find('=') {
+                Some(i) => format!("{}={}", &pair[..i], REDACTED),
+                None => pair.to_string(),
+            })
+            .collect();
+        result.push_str(&format!("?{}", pairs.join("&")));
+    }
+    result
+}
+
+/// Request body for logs, masked if it's json rpc or has secret keys
+pub fn request_body(body: &[u8]) -> String {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(value) => secret_keys(rpc_request(&value)).to_string(),
+        Err(_) => text(&String::from_utf8_lossy(body)),
+    }
+}
+
+/// Response body for logs, with results masked if request was for private keys
+pub fn response_body(request: &[u8], response: &[u8]) -> String {
+    let request = serde_json::from_slice::<Value>(request).unwrap_or(Value::Null);
+    match serde_json::from_slice::<Value>(response) {
+        Ok(response) => rpc_response(&request, response).to_string(),
+        Err(_) => text(&String::from_utf8_lossy(response)),
+    }
+}
+
+fn rpc_response(request: &Value, response: Value) -> Value {
+    match (request, response) {
+        (Value::Array(requests), Value::Array(responses)) => {
+            let secret_ids: Vec<&Value> = requests.iter().filter(|r| has_secret_result(r)).map(|r| &r["id"]).collect();
+            Value::Array(
+                responses
+                    .into_iter()
+                    .map(|response| {
+                        if secret_ids.contains(&&response["id"]) {
+                            mask_result(response)
+                        } else {
+                            response
+                        }
+                    })
+                    .collect(),
+            )
+        }
+        (request, response) => {
+            if has_secret_result(request) {
+                mask_result(response)
+            } else {
+                response
+            }
+        }
+    }
+}
+
+/// Free text, e.g. error message, with credentials of auth headers masked
+pub fn text(text: &str) -> String {
+    CREDENTIALS_REGEX
+        .replace_all(text, format!("$1 {}", REDACTED).as_str())
+        .into_owned()
+}
+
+/// Masks values of `SECRET_KEYS` at any depth
+fn secret_keys(value: Value) -> Value {
+    match value {
+        Value::Array(values) => Value::Array(values.into_iter().map(secret_keys).collect()),
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| {
+                    if SECRET_KEYS.contains(&key.as_str()) && !value.is_null() {
+                        (key, Value::String(REDACTED.to_string()))
+                    } else {
+                        (key, secret_keys(value))
+                    }
+                })
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+fn is_token(segment: &str) -> bool {
+    segment.len() >= MIN_TOKEN_LEN && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn has_secret_params(request: &Value) -> bool {
+    request["method"]
+        .as_str()
+        .map(|method| SECRET_PARAMS_METHODS.contains(&method))
+        .unwrap_or(false)
+}
+
+fn has_secret_result(request: &Value) -> bool {
+    request["method"]
+        .as_str()
+        .map(|method| SECRET_RESULT_METHODS.contains(&method))
+        .unwrap_or(false)
+}
+
+fn mask_result(mut response: Value) -> Value {
+    if let Some(result) = response.get_mut("result") {
+        if !result.is_null() {
+            *result = Value::String(REDACTED.to_string());
+        }
+    }
+    response
+}
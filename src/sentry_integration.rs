@@ -1,4 +1,9 @@
+use std::sync::Arc;
+
 use sentry;
+use sentry::protocol::Event;
+
+use redact;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct SentryConfig {
@@ -12,6 +17,7 @@ pub fn init(sentry_config: Option<&SentryConfig>) -> Option<sentry::internals::C
             config_sentry.dsn.clone(),
             sentry::ClientOptions {
                 release: sentry_crate_release!(),
+                before_send: Some(Arc::new(Box::new(redact_event))),
                 ..Default::default()
             },
         ));
@@ -19,3 +25,12 @@ pub fn init(sentry_config: Option<&SentryConfig>) -> Option<sentry::internals::C
         result
     })
 }
+
+/// Masks credentials left in error messages, e.g. auth headers in error context
+fn redact_event(mut event: Event<'static>) -> Option<Event<'static>> {
+    event.message = event.message.map(|message| redact::text(&message));
+    for exception in &mut event.exception.values {
+        exception.value = exception.value.as_ref().map(|value| redact::text(value));
+    }
+    Some(event)
+}
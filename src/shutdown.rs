@@ -0,0 +1,80 @@
+//! Graceful shutdown on SIGTERM or SIGINT, shared by the api server and background loops.
+//! Readiness turns false first, new connections are still accepted for `delay` so that
+//! load balancers stop sending traffic before the listener is closed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::Shared;
+use futures::prelude::*;
+use futures::sync::oneshot;
+use tokio::timer::Delay;
+use tokio_signal::unix::{Signal, SIGINT, SIGTERM};
+
+#[derive(Clone)]
+pub struct Shutdown {
+    started: Arc<AtomicBool>,
+    sender: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    receiver: Shared<oneshot::Receiver<()>>,
+    drained: Shared<Box<Future<Item = (), Error = ()> + Send>>,
+}
+
+impl Shutdown {
+    pub fn new(delay: Duration) -> Self {
+        let (sender, receiver) = oneshot::channel();
+        let receiver = receiver.shared();
+        // timer is created on the first poll after start, on the thread of the api server
+        let drained: Box<Future<Item = (), Error = ()> + Send> = Box::new(
+            receiver
+                .clone()
+                .then(move |_| Delay::new(Instant::now() + delay))
+                .map_err(|e| error!("Error waiting for shutdown delay: {}", e)),
+        );
+        Self {
+            started: Arc::new(AtomicBool::new(false)),
+            sender: Arc::new(Mutex::new(Some(sender))),
+            receiver,
+            drained: drained.shared(),
+        }
+    }
+
+    /// Whether shutdown started, readiness is reported as false from then on
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    pub fn start(&self) {
+        self.started.store(true, Ordering::SeqCst);
+        if let Some(sender) = self.sender.lock().unwrap().take() {
+            let _ = sender.send(());
+        }
+    }
+
+    /// Resolves when shutdown starts, can be polled from any thread
+    pub fn wait(&self) -> Box<Future<Item = (), Error = ()> + Send> {
+        Box::new(self.receiver.clone().then(|_| Ok(())))
+    }
+
+    /// Resolves `delay` after shutdown starts, when the listener is closed and in-flight requests are drained
+    pub fn drain(&self) -> Box<Future<Item = (), Error = ()> + Send> {
+        Box::new(self.drained.clone().then(|_| Ok(())))
+    }
+
+    /// Starts shutdown on the first SIGTERM or SIGINT, must be spawned on a tokio runtime
+    pub fn listen(&self) -> Box<Future<Item = (), Error = ()> + Send> {
+        let shutdown = self.clone();
+        let signals = Signal::new(SIGTERM).flatten_stream().select(Signal::new(SIGINT).flatten_stream());
+        Box::new(signals.into_future().then(move |r| {
+            match r {
+                Ok((Some(signal), _)) => {
+                    info!("Received signal {}, shutting down", signal);
+                    shutdown.start();
+                }
+                Ok((None, _)) => (),
+                Err((e, _)) => error!("Error listening for signals, graceful shutdown is disabled: {}", e),
+            }
+            Ok(())
+        }))
+    }
+}
@@ -0,0 +1,253 @@
+//! Distributed tracing of api requests, with W3C `traceparent` propagation.
+//! Finished spans are queued and exported to OpenTelemetry collector over OTLP/HTTP json.
+
+mod otlp;
+
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures::future;
+use futures::prelude::*;
+use hyper::{Body, Method, Request};
+use serde_json::Value;
+use tokio::timer::Interval;
+use tokio_core;
+use uuid::Uuid;
+
+use client::HttpClient;
+use config::Telemetry as TelemetryConfig;
+
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Identity of a span, propagated to upstream calls in `traceparent` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanContext {
+    pub trace_id: String,
+    pub span_id: String,
+    pub sampled: bool,
+}
+
+impl SpanContext {
+    /// Context of a new trace
+    fn root() -> Self {
+        Self {
+            trace_id: Uuid::new_v4().simple().to_string(),
+            span_id: new_span_id(),
+            sampled: true,
+        }
+    }
+
+    /// Parses `traceparent` header, `00-<trace id>-<parent span id>-<flags>`
+    pub fn from_traceparent(value: &str) -> Option<Self> {
+        let parts: Vec<&str> = value.trim().split('-').collect();
+        if parts.len() < 4 || parts[0].len() != 2 || parts[0] == "ff" || !is_hex(parts[0]) {
+            return None;
+        }
+        // future versions may append fields, version 00 has exactly four
+        if parts[0] == "00" && parts.len() != 4 {
+            return None;
+        }
+        let (trace_id, span_id, flags) = (parts[1], parts[2], parts[3]);
+        let valid_id = |id: &str, len: usize| id.len() == len && is_hex(id) && id.chars().any(|c| c != '0');
+        if !valid_id(trace_id, 32) || !valid_id(span_id, 16) || flags.len() != 2 || !is_hex(flags) {
+            return None;
+        }
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            sampled: flags & 1 == 1,
+        })
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.span_id, self.sampled as u8)
+    }
+
+    fn child(&self) -> Self {
+        Self {
+            span_id: new_span_id(),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Internal,
+    /// Handling of inbound request
+    Server,
+    /// Call to upstream service
+    Client,
+}
+
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub key: String,
+    pub value: Value,
+}
+
+impl Attribute {
+    pub fn new<V: Into<Value>>(key: &str, value: V) -> Self {
+        Self {
+            key: key.to_string(),
+            value: value.into(),
+        }
+    }
+
+    pub fn string(key: &str, value: &str) -> Self {
+        Self::new(key, value)
+    }
+}
+
+/// Finished span waiting for export
+#[derive(Debug, Clone)]
+pub struct SpanData {
+    pub context: SpanContext,
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub kind: SpanKind,
+    /// Unix time in nanoseconds
+    pub start: u64,
+    pub end: u64,
+    pub attributes: Vec<Attribute>,
+    pub error: Option<String>,
+}
+
+/// Span in progress, queued for export with `end`
+pub struct Span {
+    data: SpanData,
+    tracer: Tracer,
+}
+
+impl Span {
+    pub fn context(&self) -> &SpanContext {
+        &self.data.context
+    }
+
+    pub fn set_attribute<V: Into<Value>>(&mut self, key: &str, value: V) {
+        self.data.attributes.push(Attribute::new(key, value));
+    }
+
+    pub fn set_error<E: ToString>(&mut self, error: E) {
+        self.data.error = Some(error.to_string());
+    }
+
+    pub fn end(mut self) {
+        self.data.end = now();
+        self.tracer.record(self.data);
+    }
+}
+
+/// Creates spans and keeps finished ones until export. Spans are dropped if telemetry is not configured.
+#[derive(Clone)]
+pub struct Tracer {
+    config: Option<TelemetryConfig>,
+    queue: Arc<Mutex<Vec<SpanData>>>,
+}
+
+impl Tracer {
+    pub fn new(config: Option<TelemetryConfig>) -> Self {
+        Self {
+            config,
+            queue: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Starts a span, in a new trace if there is no `parent`
+    pub fn start_span(&self, name: &str, kind: SpanKind, parent: Option<&SpanContext>) -> Span {
+        let (context, parent_span_id) = match parent {
+            Some(parent) => (parent.child(), Some(parent.span_id.clone())),
+            None => (SpanContext::root(), None),
+        };
+        Span {
+            data: SpanData {
+                context,
+                parent_span_id,
+                name: name.to_string(),
+                kind,
+                start: now(),
+                end: 0,
+                attributes: vec![],
+                error: None,
+            },
+            tracer: self.clone(),
+        }
+    }
+
+    fn record(&self, span: SpanData) {
+        let max_queue = match self.config {
+            Some(ref config) if span.context.sampled => config.max_queue,
+            _ => return,
+        };
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() < max_queue {
+            queue.push(span);
+        }
+    }
+
+    fn take(&self) -> Vec<SpanData> {
+        mem::replace(&mut *self.queue.lock().unwrap(), vec![])
+    }
+}
+
+/// Spawns a thread that exports finished spans every `export_interval` seconds
+pub fn start<C: HttpClient + Clone>(tracer: Tracer, client: C) {
+    let config = match tracer.config.clone() {
+        Some(config) => config,
+        None => return,
+    };
+    let interval = Duration::from_secs(config.export_interval);
+    thread::spawn(move || {
+        let mut core = tokio_core::reactor::Core::new().unwrap();
+        core.run(
+            Interval::new(Instant::now(), interval)
+                .map_err(|e| {
+                    error!("Error creating interval {}", e);
+                })
+                .for_each(move |_| {
+                    let spans = tracer.take();
+                    if spans.is_empty() {
+                        return Box::new(future::ok(())) as Box<Future<Item = (), Error = ()> + Send>;
+                    }
+                    let count = spans.len();
+                    let body = otlp::export_request(&config.service_name, &spans).to_string();
+                    let req = Request::builder()
+                        .method(Method::POST)
+                        .uri(config.endpoint.as_str())
+                        .header("Content-Type", "application/json")
+                        .body(Body::from(body));
+                    let req = match req {
+                        Ok(req) => req,
+                        Err(e) => {
+                            error!("Error building export request to {}: {}", config.endpoint, e);
+                            return Box::new(future::ok(()));
+                        }
+                    };
+                    let endpoint = config.endpoint.clone();
+                    // collector being down shouldn't stop the next exports
+                    Box::new(client.request(req).then(move |r| {
+                        if let Err(e) = r {
+                            warn!("Couldn't export {} spans to {}: {}", count, endpoint, e);
+                        }
+                        Ok(())
+                    }))
+                }),
+        )
+    });
+}
+
+fn new_span_id() -> String {
+    Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+fn is_hex(s: &str) -> bool {
+    s.chars().all(|c| c.is_digit(16) && !c.is_uppercase())
+}
+
+fn now() -> u64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_secs() * 1_000_000_000 + u64::from(since_epoch.subsec_nanos())
+}
@@ -0,0 +1,54 @@
+//! Encoding of spans as OTLP/HTTP json export request.
+
+use serde_json::Value;
+
+use super::{Attribute, SpanData, SpanKind};
+
+/// `ExportTraceServiceRequest` with all spans of one service
+pub fn export_request(service_name: &str, spans: &[SpanData]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute(&Attribute::string("service.name", service_name))]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "bitcoin_proxy" },
+                "spans": spans.iter().map(span).collect::<Vec<_>>()
+            }]
+        }]
+    })
+}
+
+fn span(span: &SpanData) -> Value {
+    let kind = match span.kind {
+        SpanKind::Internal => 1,
+        SpanKind::Server => 2,
+        SpanKind::Client => 3,
+    };
+    json!({
+        "traceId": span.context.trace_id,
+        "spanId": span.context.span_id,
+        "parentSpanId": span.parent_span_id.clone().unwrap_or_default(),
+        "name": span.name,
+        "kind": kind,
+        // 64 bit integers are strings in OTLP json
+        "startTimeUnixNano": span.start.to_string(),
+        "endTimeUnixNano": span.end.to_string(),
+        "attributes": span.attributes.iter().map(attribute).collect::<Vec<_>>(),
+        "status": match span.error {
+            Some(ref message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 1 }),
+        }
+    })
+}
+
+fn attribute(attribute: &Attribute) -> Value {
+    let value = match attribute.value {
+        Value::String(ref value) => json!({ "stringValue": value }),
+        Value::Bool(value) => json!({ "boolValue": value }),
+        Value::Number(ref value) if value.is_i64() || value.is_u64() => json!({ "intValue": value.to_string() }),
+        Value::Number(ref value) => json!({ "doubleValue": value }),
+        ref value => json!({ "stringValue": value.to_string() }),
+    };
+    json!({ "key": attribute.key, "value": value })
+}
@@ -0,0 +1,378 @@
+mod error;
+mod state;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono;
+use futures::future::{self, Either};
+use futures::prelude::*;
+use tokio::timer::Interval;
+use tokio_core;
+
+use client::{BitcoinClient, BitcoinClientErrorKind, BitcoinClientImpl, Block, HttpClient};
+use config::Tracking as TrackingConfig;
+use models::*;
+use utils::{log_error, log_warn};
+
+use self::state::StateStore;
+
+/// Maximum number of blocks scanned for tracked transactions in one poll
+const MAX_BLOCKS_PER_POLL: u64 = 20;
+/// `RPC_VERIFY_ERROR` in bitcoind, returned e.g. when inputs are missing or already spent
+const RPC_VERIFY_ERROR: i64 = -25;
+/// `RPC_VERIFY_REJECTED` in bitcoind, returned when transaction is rejected by mempool policy
+const RPC_VERIFY_REJECTED: i64 = -26;
+/// `RPC_VERIFY_ALREADY_IN_CHAIN` in bitcoind
+const RPC_VERIFY_ALREADY_IN_CHAIN: i64 = -27;
+
+/// Result of checking tracked transaction on one node
+enum NodeCheck {
+    InMempool(String),
+    Rebroadcast(String),
+    Rejected(String, BroadcastRejection),
+    /// Not in mempool, rebroadcast to this node is postponed or given up
+    Missing,
+    Unknown,
+}
+
+/// Watches transactions submitted through the proxy: follows them into blocks,
+/// checks they stay in mempools of healthy nodes and rebroadcasts them where missing.
+#[derive(Clone)]
+pub struct TxTracker {
+    config: TrackingConfig,
+    http_client: Arc<HttpClient>,
+    nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    transactions: Arc<Mutex<BTreeMap<String, TrackedTransaction>>>,
+    scanned_height: Arc<Mutex<Option<u64>>>,
+    state: Option<StateStore>,
+}
+
+impl TxTracker {
+    pub fn new(config: TrackingConfig, http_client: Arc<HttpClient>, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>) -> Self {
+        let state = config.state_path.clone().map(StateStore::new);
+        let (transactions, scanned_height) = match state.as_ref().map(|state| state.restore()) {
+            Some(Ok(restored)) => restored,
+            Some(Err(e)) => {
+                log_error(&e);
+                (BTreeMap::new(), None)
+            }
+            None => (BTreeMap::new(), None),
+        };
+        Self {
+            config,
+            http_client,
+            nodes,
+            transactions: Arc::new(Mutex::new(transactions)),
+            scanned_height: Arc::new(Mutex::new(scanned_height)),
+            state,
+        }
+    }
+
+    pub fn track(&self, txid: String, hex: String) {
+        let now = chrono::Utc::now().naive_utc();
+        self.transactions
+            .lock()
+            .unwrap()
+            .entry(txid.clone())
+            .or_insert_with(|| TrackedTransaction::new(txid, hex, now));
+        self.save_state();
+    }
+
+    pub fn get(&self, txid: &str) -> Option<TrackedTransaction> {
+        self.transactions.lock().unwrap().get(txid).cloned()
+    }
+
+    pub fn list(&self) -> Vec<TrackedTransaction> {
+        self.transactions.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Updates confirmations, checks mempools, rebroadcasts missing transactions and forgets old ones
+    pub fn poll(&self) -> impl Future<Item = (), Error = ()> + Send {
+        let self_clone = self.clone();
+        let self_clone2 = self.clone();
+        self.scan_blocks().then(move |_| self_clone.check_mempools()).map(move |_| {
+            self_clone2.forget_old();
+            self_clone2.save_state();
+        })
+    }
+
+    /// Persists tracked transactions, if configured, so that they survive restart
+    fn save_state(&self) {
+        if let Some(ref state) = self.state {
+            let transactions = self.transactions.lock().unwrap();
+            let scanned_height = *self.scanned_height.lock().unwrap();
+            if let Err(e) = state.save(&transactions, scanned_height) {
+                log_error(&e);
+            }
+        }
+    }
+
+    fn scan_blocks(&self) -> Box<Future<Item = (), Error = ()> + Send> {
+        let client = self.main_client();
+        let self_clone = self.clone();
+        Box::new(client.get_block_count().map_err(|e| log_warn(&e)).and_then(move |tip| {
+            let from = self_clone.scanned_height.lock().unwrap().map(|height| height + 1).unwrap_or(tip);
+            let to = tip.min(from + MAX_BLOCKS_PER_POLL - 1);
+            let blocks: Vec<_> = (from..=to)
+                .map(|height| {
+                    let client_clone = client.clone();
+                    client.get_block_hash(height).and_then(move |hash| client_clone.get_block(hash))
+                })
+                .collect();
+            let mut confirmed_heights: Vec<u64> = self_clone
+                .transactions
+                .lock()
+                .unwrap()
+                .values()
+                .filter_map(|tx| tx.block.as_ref().map(|block| block.height))
+                .filter(|height| *height <= tip)
+                .collect();
+            confirmed_heights.sort();
+            confirmed_heights.dedup();
+            let hashes: Vec<_> = confirmed_heights
+                .into_iter()
+                .map(|height| client.get_block_hash(height).map(move |hash| (height, hash)))
+                .collect();
+            future::join_all(blocks)
+                .join(future::join_all(hashes))
+                .map_err(|e| log_warn(&e))
+                .map(move |(blocks, hashes)| self_clone.apply_blocks(tip, to, blocks, hashes))
+        }))
+    }
+
+    fn apply_blocks(&self, tip: u64, scanned_to: u64, blocks: Vec<Block>, hashes: Vec<(u64, String)>) {
+        let hashes: HashMap<u64, String> = hashes.into_iter().collect();
+        let mut transactions = self.transactions.lock().unwrap();
+        let mut scanned_to = scanned_to;
+        for tx in transactions.values_mut() {
+            let reorged = match tx.block {
+                Some(ref block) => hashes.get(&block.height).map(|hash| *hash != block.hash).unwrap_or(false),
+                None => false,
+            };
+            if reorged {
+                let height = tx.block.as_ref().map(|block| block.height).unwrap_or(0);
+                warn!("Tracked transaction {} was reorged out of block at height {}", tx.txid, height);
+                scanned_to = scanned_to.min(height.saturating_sub(1));
+                tx.block = None;
+                tx.confirmations = 0;
+                tx.status = TransactionStatus::Pending;
+            }
+        }
+        for block in &blocks {
+            for txid in &block.tx {
+                if let Some(tx) = transactions.get_mut(txid) {
+                    tx.block = Some(TransactionBlock {
+                        height: block.height,
+                        hash: block.hash.clone(),
+                    });
+                    tx.status = TransactionStatus::Confirmed;
+                }
+            }
+        }
+        for tx in transactions.values_mut() {
+            if let Some(ref block) = tx.block {
+                tx.confirmations = (tip + 1).saturating_sub(block.height);
+            }
+        }
+        *self.scanned_height.lock().unwrap() = Some(scanned_to);
+    }
+
+    fn check_mempools(&self) -> impl Future<Item = (), Error = ()> + Send {
+        let unconfirmed: Vec<TrackedTransaction> = self
+            .transactions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|tx| tx.block.is_none())
+            .cloned()
+            .collect();
+        let nodes = {
+            let mut nodes = self.nodes.lock().unwrap();
+            let healthy = healthy_nodes(&nodes);
+            if healthy.is_empty() {
+                vec![active_node(&mut nodes)]
+            } else {
+                healthy
+            }
+        };
+        let now = chrono::Utc::now().naive_utc();
+        let mut checks = Vec::new();
+        for tx in &unconfirmed {
+            for node in &nodes {
+                let txid = tx.txid.clone();
+                let may_rebroadcast = self.may_rebroadcast(tx, &node.url, now);
+                checks.push(self.check_node(node, tx, may_rebroadcast).map(move |check| (txid, check)));
+            }
+        }
+        let self_clone = self.clone();
+        future::join_all(checks).map(move |checks| self_clone.apply_checks(checks))
+    }
+
+    /// Rebroadcasts to a node are limited to `max_rebroadcasts`, with delay doubling after each one
+    fn may_rebroadcast(&self, tx: &TrackedTransaction, url: &str, now: chrono::NaiveDateTime) -> bool {
+        match tx.rebroadcasts_to.get(url) {
+            None => true,
+            Some(rebroadcast) if rebroadcast.attempts >= self.config.max_rebroadcasts => false,
+            Some(rebroadcast) => {
+                let delay = 2i64
+                    .checked_pow(rebroadcast.attempts.saturating_sub(1))
+                    .and_then(|multiplier| (self.config.interval as i64).checked_mul(multiplier))
+                    .unwrap_or(self.config.max_age)
+                    .min(self.config.max_age);
+                now - rebroadcast.last_at >= chrono::Duration::seconds(delay)
+            }
+        }
+    }
+
+    fn check_node(
+        &self,
+        node: &BitcoinNode,
+        tx: &TrackedTransaction,
+        may_rebroadcast: bool,
+    ) -> impl Future<Item = NodeCheck, Error = ()> + Send {
+        let client = self.client(node);
+        let client_clone = client.clone();
+        let url = node.url.clone();
+        let hex = tx.hex.clone();
+        client.get_mempool_entry(tx.txid.clone()).then(move |r| match r {
+            Ok(Some(_)) => Either::A(future::ok(NodeCheck::InMempool(url))),
+            Ok(None) if !may_rebroadcast => Either::A(future::ok(NodeCheck::Missing)),
+            Ok(None) => Either::B(client_clone.send_raw_transaction(json!([hex])).then(move |r| {
+                Ok::<_, ()>(match r {
+                    Ok(_) => NodeCheck::Rebroadcast(url),
+                    Err(e) => match e.kind() {
+                        BitcoinClientErrorKind::Rpc(rpc_error) => NodeCheck::Rejected(
+                            url,
+                            BroadcastRejection {
+                                code: rpc_error.code,
+                                message: rpc_error.message,
+                            },
+                        ),
+                        _ => {
+                            log_warn(&e);
+                            NodeCheck::Unknown
+                        }
+                    },
+                })
+            })),
+            Err(e) => {
+                log_warn(&e);
+                Either::A(future::ok(NodeCheck::Unknown))
+            }
+        })
+    }
+
+    fn apply_checks(&self, checks: Vec<(String, NodeCheck)>) {
+        let now = chrono::Utc::now().naive_utc();
+        let mut by_txid: HashMap<String, Vec<NodeCheck>> = HashMap::new();
+        for (txid, check) in checks {
+            by_txid.entry(txid).or_insert_with(Vec::new).push(check);
+        }
+        let mut transactions = self.transactions.lock().unwrap();
+        for (txid, checks) in by_txid {
+            let tx = match transactions.get_mut(&txid) {
+                Some(tx) => tx,
+                None => continue,
+            };
+            tx.checked_at = Some(now);
+            // confirmed while we were checking mempools
+            if tx.block.is_some() {
+                continue;
+            }
+            let mut in_mempool_of = Vec::new();
+            let mut rejections = Vec::new();
+            for check in checks {
+                match check {
+                    NodeCheck::InMempool(url) => in_mempool_of.push(url),
+                    NodeCheck::Rebroadcast(url) => {
+                        info!("Rebroadcasted transaction {} to node {}", txid, url);
+                        tx.rebroadcasts += 1;
+                        record_rebroadcast(tx, &url, now);
+                        in_mempool_of.push(url);
+                    }
+                    NodeCheck::Rejected(url, rejection) => {
+                        record_rebroadcast(tx, &url, now);
+                        rejections.push(rejection);
+                    }
+                    NodeCheck::Missing | NodeCheck::Unknown => (),
+                }
+            }
+            let already_in_chain = rejections.iter().any(|r| r.code == RPC_VERIFY_ALREADY_IN_CHAIN);
+            let conflicted = rejections.iter().any(is_conflict);
+            if let Some(rejection) = rejections.into_iter().nth(0) {
+                tx.last_rejection = Some(rejection);
+            }
+            tx.status = if !in_mempool_of.is_empty() || already_in_chain {
+                // transaction already in chain is confirmed on the next block scan
+                TransactionStatus::Pending
+            } else if conflicted {
+                TransactionStatus::Conflicted
+            } else if tx.last_rejection.is_some() {
+                TransactionStatus::Evicted
+            } else {
+                tx.status
+            };
+            tx.in_mempool_of = in_mempool_of;
+        }
+    }
+
+    fn forget_old(&self) {
+        let now = chrono::Utc::now().naive_utc();
+        let max_age = chrono::Duration::seconds(self.config.max_age);
+        let mut transactions = self.transactions.lock().unwrap();
+        let expired: Vec<String> = transactions
+            .values()
+            .filter(|tx| {
+                tx.confirmations >= self.config.forget_after_confirmations || (tx.block.is_none() && now - tx.submitted_at > max_age)
+            })
+            .map(|tx| tx.txid.clone())
+            .collect();
+        for txid in expired {
+            debug!("Stopped tracking transaction {}", txid);
+            transactions.remove(&txid);
+        }
+    }
+
+    fn main_client(&self) -> BitcoinClientImpl {
+        let node = {
+            let mut nodes = self.nodes.lock().unwrap();
+            active_node(&mut nodes)
+        };
+        self.client(&node)
+    }
+
+    fn client(&self, node: &BitcoinNode) -> BitcoinClientImpl {
+        BitcoinClientImpl::from_node(self.http_client.clone(), node)
+    }
+}
+
+fn is_conflict(rejection: &BroadcastRejection) -> bool {
+    rejection.code == RPC_VERIFY_ERROR || (rejection.code == RPC_VERIFY_REJECTED && rejection.message.contains("conflict"))
+}
+
+fn record_rebroadcast(tx: &mut TrackedTransaction, url: &str, now: chrono::NaiveDateTime) {
+    let rebroadcast = tx
+        .rebroadcasts_to
+        .entry(url.to_string())
+        .or_insert_with(|| NodeRebroadcast { attempts: 0, last_at: now });
+    rebroadcast.attempts += 1;
+    rebroadcast.last_at = now;
+}
+
+/// Spawns a thread that periodically polls tracked transactions
+pub fn start(config: TrackingConfig, tracker: TxTracker) {
+    let interval = Duration::from_secs(config.interval);
+    thread::spawn(move || {
+        let mut core = tokio_core::reactor::Core::new().unwrap();
+        core.run(
+            Interval::new(Instant::now(), interval)
+                .map_err(|e| {
+                    error!("Error creating interval {}", e);
+                })
+                .for_each(move |_| tracker.poll()),
+        )
+    });
+}
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use failure::Fail;
+use serde_json;
+
+use super::error::*;
+use models::*;
+
+/// Tracked transaction with its hex, which is hidden from the api
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SavedTransaction {
+    hex: String,
+    transaction: TrackedTransaction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SavedState {
+    scanned_height: Option<u64>,
+    transactions: Vec<SavedTransaction>,
+}
+
+/// Keeps tracked transactions in a json file, so that they are still followed and rebroadcasted after restart
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    path: String,
+}
+
+impl StateStore {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+
+    /// Writes transactions to a temporary file and renames it, so that the file is never left half written
+    pub fn save(&self, transactions: &BTreeMap<String, TrackedTransaction>, scanned_height: Option<u64>) -> Result<(), Error> {
+        let path = &self.path;
+        let state = SavedState {
+            scanned_height,
+            transactions: transactions
+                .values()
+                .map(|tx| SavedTransaction {
+                    hex: tx.hex.clone(),
+                    transaction: tx.clone(),
+                })
+                .collect(),
+        };
+        let data = serde_json::to_vec(&state).map_err(ectx!(try ErrorContext::SaveState, ErrorSource::Json, ErrorKind::Internal))?;
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, data)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(ectx!(ErrorContext::SaveState, ErrorSource::Io, ErrorKind::Internal => path))
+    }
+
+    /// Returns saved transactions with the height blocks were scanned to
+    pub fn restore(&self) -> Result<(BTreeMap<String, TrackedTransaction>, Option<u64>), Error> {
+        let path = &self.path;
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("No saved tracked transactions at {}", path);
+                return Ok((BTreeMap::new(), None));
+            }
+            Err(e) => return Err(ectx!(err e, ErrorContext::LoadState, ErrorSource::Io, ErrorKind::Internal => path)),
+        };
+        let state: SavedState =
+            serde_json::from_slice(&data).map_err(ectx!(try ErrorContext::LoadState, ErrorSource::Json, ErrorKind::Internal => path))?;
+        let transactions: BTreeMap<String, TrackedTransaction> = state
+            .transactions
+            .into_iter()
+            .map(|saved| {
+                let mut tx = saved.transaction;
+                tx.hex = saved.hex;
+                (tx.txid.clone(), tx)
+            })
+            .collect();
+        info!("Restored {} tracked transactions", transactions.len());
+        Ok((transactions, state.scanned_height))
+    }
+}
@@ -0,0 +1,354 @@
+//! Watches of addresses and transactions, reporting when they are first seen,
+//! each confirmation up to the requested depth and reorgs that unconfirm them.
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono;
+use futures::future;
+use futures::prelude::*;
+use futures::stream;
+use serde_json;
+use tokio::timer::Interval;
+use tokio_core;
+use uuid::Uuid;
+
+use client::{BitcoinClient, BitcoinClientImpl, HttpClient, RawTransaction, VerboseBlock, WebhookClientImpl};
+use config::{Watches as WatchesConfig, WebhookSubscriber};
+use models::*;
+use notifications::NotificationHub;
+use utils::log_warn;
+use webhooks::{deliver, ChainWatcher};
+
+/// Watch registry and scanner of new blocks and mempool
+#[derive(Clone)]
+pub struct WatchService {
+    config: WatchesConfig,
+    http_client: Arc<HttpClient>,
+    nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    chain: ChainWatcher,
+    hub: NotificationHub,
+    watches: Arc<Mutex<BTreeMap<Uuid, Watch>>>,
+    /// Mempool txids seen on the previous scan, `None` before the first one
+    mempool: Arc<Mutex<Option<HashSet<String>>>>,
+}
+
+impl WatchService {
+    pub fn new(
+        config: WatchesConfig,
+        http_client: Arc<HttpClient>,
+        nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+        hub: NotificationHub,
+    ) -> Self {
+        Self {
+            config,
+            chain: ChainWatcher::new(http_client.clone(), nodes.clone()),
+            http_client,
+            nodes,
+            hub,
+            watches: Arc::new(Mutex::new(BTreeMap::new())),
+            mempool: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Upper limit for confirmations requested by a watch
+    pub fn max_confirmations(&self) -> u64 {
+        self.config.max_confirmations
+    }
+
+    pub fn add(&self, new_watch: NewWatch) -> Watch {
+        let watch = Watch::new(new_watch, chrono::Utc::now().naive_utc());
+        self.watches.lock().unwrap().insert(watch.id, watch.clone());
+        watch
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<Watch> {
+        self.watches.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Watch> {
+        self.watches.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn remove(&self, id: Uuid) -> Option<Watch> {
+        self.watches.lock().unwrap().remove(&id)
+    }
+
+    /// Whether anyone may set callback url to this host
+    pub fn callback_host_allowed(&self, host: &str) -> bool {
+        self.config.callback_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+
+    /// Forgets watches older than `ttl` and transaction watches that reached requested confirmations
+    pub fn expire(&self) {
+        let now = chrono::Utc::now().naive_utc();
+        let ttl = chrono::Duration::seconds(self.config.ttl);
+        let mut watches = self.watches.lock().unwrap();
+        let expired: Vec<Uuid> = watches
+            .values()
+            .filter(|watch| {
+                let confirmed = watch.txid.is_some()
+                    && !watch.transactions.is_empty()
+                    && watch.transactions.iter().all(|tx| tx.confirmations >= watch.confirmations);
+                confirmed || now - watch.created_at > ttl
+            })
+            .map(|watch| watch.id)
+            .collect();
+        for id in expired {
+            debug!("Watch {} expired", id);
+            watches.remove(&id);
+        }
+    }
+
+    /// Scans chain changes and new mempool transactions, returning events to deliver
+    pub fn poll(&self) -> impl Future<Item = Vec<WatchEvent>, Error = ()> + Send {
+        let client = self.bitcoin_client();
+        let client_clone = client.clone();
+        let self_clone = self.clone();
+        let self_clone2 = self.clone();
+        self.chain
+            .changes()
+            .and_then(move |update| {
+                let chain_events = update.events();
+                let blocks: Vec<_> = chain_events
+                    .iter()
+                    .filter_map(|event| match event {
+                        ChainEvent::Block { hash, .. } => Some(client.get_verbose_block(hash.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                // chain moves on only when all blocks are fetched, otherwise the same changes are scanned next time
+                future::join_all(blocks).map_err(|e| log_warn(&e)).map(move |blocks| {
+                    self_clone.chain.apply(&update);
+                    self_clone.apply_chain(chain_events, blocks)
+                })
+            })
+            .then(move |r| {
+                let events = r.unwrap_or_default();
+                self_clone2
+                    .scan_mempool(client_clone)
+                    .then(move |r| Ok(events.into_iter().chain(r.unwrap_or_default()).collect()))
+            })
+    }
+
+    fn apply_chain(&self, chain_events: Vec<ChainEvent>, blocks: Vec<VerboseBlock>) -> Vec<WatchEvent> {
+        let mut watches = self.watches.lock().unwrap();
+        let mut events = Vec::new();
+        for chain_event in chain_events {
+            if let ChainEvent::Reorg { disconnected, .. } = chain_event {
+                for watch in watches.values_mut() {
+                    for i in 0..watch.transactions.len() {
+                        let reorged = match watch.transactions[i].block {
+                            Some(ref block) => disconnected.contains(&block.hash),
+                            None => false,
+                        };
+                        if reorged {
+                            watch.transactions[i].block = None;
+                            watch.transactions[i].confirmations = 0;
+                            events.push(WatchEvent::new(watch, WatchEventKind::Unconfirmed, &watch.transactions[i]));
+                        }
+                    }
+                }
+            }
+        }
+        for block in &blocks {
+            let tx_block = TransactionBlock {
+                height: block.height,
+                hash: block.hash.clone(),
+            };
+            for tx in &block.tx {
+                for watch in watches.values_mut() {
+                    if let Some(event) = match_transaction(watch, tx, Some(tx_block.clone())) {
+                        events.push(event);
+                    }
+                }
+            }
+        }
+        if let Some(tip) = self.chain.tip() {
+            for watch in watches.values_mut() {
+                for i in 0..watch.transactions.len() {
+                    let depth = match watch.transactions[i].block {
+                        Some(ref block) => (tip + 1).saturating_sub(block.height).min(watch.confirmations),
+                        None => continue,
+                    };
+                    while watch.transactions[i].confirmations < depth {
+                        watch.transactions[i].confirmations += 1;
+                        events.push(WatchEvent::new(watch, WatchEventKind::Confirmed, &watch.transactions[i]));
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    fn scan_mempool(&self, client: BitcoinClientImpl) -> Box<Future<Item = Vec<WatchEvent>, Error = ()> + Send> {
+        let self_clone = self.clone();
+        let max_transactions = self.config.max_mempool_transactions;
+        let concurrency = self.config.mempool_concurrency.max(1);
+        let watches_addresses = self.watches.lock().unwrap().values().any(|watch| watch.address.is_some());
+        Box::new(client.get_raw_mempool().map_err(|e| log_warn(&e)).and_then(move |txids| {
+            let new_txids: Vec<String> = {
+                let mut mempool = self_clone.mempool.lock().unwrap();
+                // transactions already in mempool on start are not reported as new
+                let new_txids = match *mempool {
+                    Some(ref seen) => txids.iter().filter(|txid| !seen.contains(*txid)).cloned().collect(),
+                    None => vec![],
+                };
+                *mempool = Some(txids.into_iter().collect());
+                new_txids
+            };
+            let mut events = self_clone.match_txids(&new_txids);
+            if !watches_addresses {
+                return future::Either::A(future::ok(events));
+            }
+            // decoding is needed only to match outputs against watched addresses,
+            // a few calls at a time so that rpc work queue of the node isn't exhausted
+            let txids: Vec<String> = new_txids.into_iter().take(max_transactions).collect();
+            let txs = stream::iter_ok(txids)
+                .map(move |txid| client.get_raw_transaction(txid).then(|r| Ok::<_, ()>(r.ok())))
+                .buffered(concurrency);
+            future::Either::B(txs.collect().map(move |txs| {
+                let mut watches = self_clone.watches.lock().unwrap();
+                for tx in txs.into_iter().filter_map(|tx| tx) {
+                    for watch in watches.values_mut().filter(|watch| watch.address.is_some()) {
+                        if let Some(event) = match_transaction(watch, &tx, None) {
+                            events.push(event);
+                        }
+                    }
+                }
+                events
+            }))
+        }))
+    }
+
+    /// Matches txid watches against mempool transactions, which doesn't need decoding
+    fn match_txids(&self, txids: &[String]) -> Vec<WatchEvent> {
+        let mut watches = self.watches.lock().unwrap();
+        let mut events = Vec::new();
+        for watch in watches.values_mut() {
+            let seen = match watch.txid {
+                Some(ref txid) => txids.contains(txid) && watch.transactions.is_empty(),
+                None => false,
+            };
+            if seen {
+                let tx = WatchedTransaction {
+                    txid: watch.txid.clone().unwrap_or_default(),
+                    value: None,
+                    block: None,
+                    confirmations: 0,
+                };
+                events.push(WatchEvent::new(watch, WatchEventKind::Seen, &tx));
+                watch.transactions.push(tx);
+            }
+        }
+        events
+    }
+
+    /// Sends events of one watch, in order, to its callback url or to websocket subscribers of `watch:<id>`
+    fn deliver(&self, handle: &tokio_core::reactor::Handle, client: &WebhookClientImpl, watch_id: Uuid, events: Vec<WatchEvent>) {
+        let watch = match self.get(watch_id) {
+            Some(watch) => watch,
+            None => return,
+        };
+        match watch.callback_url {
+            Some(url) => {
+                let subscriber = WebhookSubscriber { url, secret: watch.secret };
+                let retry = self.config.retry.clone();
+                let client = client.clone();
+                handle.spawn(
+                    stream::iter_ok(events)
+                        .for_each(move |event| deliver(client.clone(), subscriber.clone(), retry.clone(), &WebhookPayload::new(event))),
+                );
+            }
+            None => {
+                let node = self.active_node().url;
+                for event in events {
+                    self.hub.publish(Notification {
+                        topic: format!("watch:{}", watch.id),
+                        data: serde_json::to_string(&event).unwrap_or_default(),
+                        node: node.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn active_node(&self) -> BitcoinNode {
+        let mut nodes = self.nodes.lock().unwrap();
+        active_node(&mut nodes)
+    }
+
+    fn bitcoin_client(&self) -> BitcoinClientImpl {
+        let node = self.active_node();
+        BitcoinClientImpl::from_node(self.http_client.clone(), &node)
+    }
+}
+
+/// Records transaction in watch if it matches, returning `Seen` event for new transactions
+fn match_transaction(watch: &mut Watch, tx: &RawTransaction, block: Option<TransactionBlock>) -> Option<WatchEvent> {
+    let value = match (watch.address.as_ref(), watch.txid.as_ref()) {
+        (Some(address), _) => {
+            let outputs: Vec<_> = tx
+                .vout
+                .iter()
+                .filter(|output| output.script_pub_key.address().as_ref() == Some(address))
+                .collect();
+            if outputs.is_empty() {
+                return None;
+            }
+            Some(outputs.iter().map(|output| (output.value * 100_000_000.0).round() as u64).sum())
+        }
+        (None, Some(txid)) if *txid == tx.txid => None,
+        _ => return None,
+    };
+    if let Some(known) = watch.transactions.iter_mut().find(|known| known.txid == tx.txid) {
+        if block.is_some() {
+            known.block = block;
+        }
+        return None;
+    }
+    let watched = WatchedTransaction {
+        txid: tx.txid.clone(),
+        value,
+        block,
+        confirmations: 0,
+    };
+    let event = WatchEvent::new(watch, WatchEventKind::Seen, &watched);
+    watch.transactions.push(watched);
+    Some(event)
+}
+
+/// Spawns a thread that scans for watched addresses and transactions and delivers events
+pub fn start<C: HttpClient + Clone>(config: WatchesConfig, watches: WatchService, client: C) {
+    let interval = Duration::from_secs(config.interval);
+    thread::spawn(move || {
+        let mut core = tokio_core::reactor::Core::new().unwrap();
+        let handle = core.handle();
+        let webhook_client = WebhookClientImpl::new(Arc::new(client));
+        core.run(
+            Interval::new(Instant::now(), interval)
+                .map_err(|e| {
+                    error!("Error creating interval {}", e);
+                })
+                .for_each(move |_| {
+                    let handle = handle.clone();
+                    let webhook_client = webhook_client.clone();
+                    let watches_clone = watches.clone();
+                    watches.poll().then(move |r| {
+                        let mut by_watch: BTreeMap<Uuid, Vec<WatchEvent>> = BTreeMap::new();
+                        for event in r.unwrap_or_default() {
+                            by_watch.entry(event.watch_id).or_insert_with(Vec::new).push(event);
+                        }
+                        for (watch_id, events) in by_watch {
+                            watches_clone.deliver(&handle, &webhook_client, watch_id, events);
+                        }
+                        // after delivery, which needs the watch to find its callback
+                        watches_clone.expire();
+                        Ok(())
+                    })
+                }),
+        )
+    });
+}
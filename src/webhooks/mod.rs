@@ -0,0 +1,269 @@
+//! Webhooks about new blocks and reorgs of the best chain, detected by polling the active node.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, Either, Loop};
+use futures::prelude::*;
+use futures::sync::mpsc;
+use serde::Serialize;
+use serde_json;
+use tokio::timer::{Delay, Interval};
+use tokio_core;
+
+use client::{BitcoinClient, BitcoinClientImpl, HttpClient, WebhookClient, WebhookClientImpl};
+use config::{WebhookRetry, WebhookSubscriber, Webhooks as WebhooksConfig};
+use models::*;
+use utils::log_warn;
+
+/// Number of recent blocks remembered to find the fork point on reorg
+const CHAIN_DEPTH: usize = 100;
+/// Maximum number of new blocks reported in one poll
+const MAX_BLOCKS_PER_POLL: u64 = 20;
+
+/// Change of the best chain found by `ChainWatcher::changes`, not yet applied to the watcher
+#[derive(Debug, Clone, Default)]
+pub struct ChainUpdate {
+    fork_height: u64,
+    /// Remembered blocks above the fork point, newest first
+    disconnected: Vec<String>,
+    /// New blocks as (height, hash), ordered by height
+    blocks: Vec<(u64, String)>,
+    /// First poll only remembers the tip, without events
+    initial: bool,
+}
+
+impl ChainUpdate {
+    pub fn events(&self) -> Vec<ChainEvent> {
+        if self.initial {
+            return vec![];
+        }
+        let mut events = Vec::new();
+        if !self.disconnected.is_empty() {
+            events.push(ChainEvent::Reorg {
+                fork_height: self.fork_height,
+                disconnected: self.disconnected.clone(),
+            });
+        }
+        events.extend(self.blocks.iter().map(|(height, hash)| ChainEvent::Block {
+            height: *height,
+            hash: hash.clone(),
+        }));
+        events
+    }
+}
+
+/// Follows the best chain of the active node and turns its changes into `ChainEvent`s
+#[derive(Clone)]
+pub struct ChainWatcher {
+    http_client: Arc<HttpClient>,
+    nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>,
+    /// Recent blocks of the best chain as (height, hash), ordered by height
+    chain: Arc<Mutex<VecDeque<(u64, String)>>>,
+}
+
+impl ChainWatcher {
+    pub fn new(http_client: Arc<HttpClient>, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>) -> Self {
+        Self {
+            http_client,
+            nodes,
+            chain: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Height of the last block seen in the best chain
+    pub fn tip(&self) -> Option<u64> {
+        self.chain.lock().unwrap().back().map(|(height, _)| *height)
+    }
+
+    /// Returns events since the previous poll. The first poll only remembers current tip.
+    pub fn poll(&self) -> Box<Future<Item = Vec<ChainEvent>, Error = ()> + Send> {
+        let self_clone = self.clone();
+        Box::new(self.changes().map(move |update| {
+            self_clone.apply(&update);
+            update.events()
+        }))
+    }
+
+    /// Finds changes since the last applied update. They are found again on the next call until applied,
+    /// so that callers failing to process them don't lose events.
+    pub fn changes(&self) -> Box<Future<Item = ChainUpdate, Error = ()> + Send> {
+        let client = self.bitcoin_client();
+        let self_clone = self.clone();
+        Box::new(client.get_last_block().map_err(|e| log_warn(&e)).and_then(move |tip| {
+            let known_tip = self_clone.chain.lock().unwrap().back().map(|(height, _)| *height);
+            let known_tip = match known_tip {
+                Some(known_tip) => known_tip,
+                None => {
+                    return Either::A(client.get_block_hash(tip).map_err(|e| log_warn(&e)).map(move |hash| ChainUpdate {
+                        fork_height: tip,
+                        blocks: vec![(tip, hash)],
+                        initial: true,
+                        ..Default::default()
+                    }));
+                }
+            };
+            let self_clone2 = self_clone.clone();
+            Either::B(
+                self_clone
+                    .find_fork(client.clone(), tip.min(known_tip))
+                    .and_then(move |fork_height| {
+                        // node is behind us, e.g. right after failover to a lagging node
+                        if fork_height == tip && tip < known_tip {
+                            return Either::A(future::ok(ChainUpdate::default()));
+                        }
+                        let to = tip.min(fork_height + MAX_BLOCKS_PER_POLL);
+                        let hashes: Vec<_> = (fork_height + 1..=to)
+                            .map(|height| client.get_block_hash(height).map(move |hash| (height, hash)))
+                            .collect();
+                        Either::B(
+                            future::join_all(hashes)
+                                .map_err(|e| log_warn(&e))
+                                .map(move |blocks| self_clone2.update(fork_height, blocks)),
+                        )
+                    }),
+            )
+        }))
+    }
+
+    /// Walks down from `height` until node's block hash matches the remembered one
+    fn find_fork(&self, client: BitcoinClientImpl, height: u64) -> impl Future<Item = u64, Error = ()> + Send {
+        let chain = self.chain.clone();
+        future::loop_fn(height, move |height| {
+            let chain = chain.clone();
+            client.get_block_hash(height).map_err(|e| log_warn(&e)).map(move |hash| {
+                let chain = chain.lock().unwrap();
+                let lowest = chain.front().map(|(height, _)| *height).unwrap_or(0);
+                let known = chain.iter().find(|(h, _)| *h == height).map(|(_, hash)| hash);
+                if known == Some(&hash) || height <= lowest || height == 0 {
+                    Loop::Break(height)
+                } else {
+                    Loop::Continue(height - 1)
+                }
+            })
+        })
+    }
+
+    fn update(&self, fork_height: u64, blocks: Vec<(u64, String)>) -> ChainUpdate {
+        let chain = self.chain.lock().unwrap();
+        let disconnected = chain
+            .iter()
+            .rev()
+            .take_while(|(height, _)| *height > fork_height)
+            .map(|(_, hash)| hash.clone())
+            .collect();
+        ChainUpdate {
+            fork_height,
+            disconnected,
+            blocks,
+            initial: false,
+        }
+    }
+
+    /// Moves remembered chain to the state after `update`
+    pub fn apply(&self, update: &ChainUpdate) {
+        let mut chain = self.chain.lock().unwrap();
+        if !update.disconnected.is_empty() {
+            warn!("Reorg of {} blocks above height {}", update.disconnected.len(), update.fork_height);
+        }
+        for _ in &update.disconnected {
+            chain.pop_back();
+        }
+        chain.extend(update.blocks.iter().cloned());
+        while chain.len() > CHAIN_DEPTH {
+            chain.pop_front();
+        }
+    }
+
+    fn bitcoin_client(&self) -> BitcoinClientImpl {
+        let node = {
+            let mut nodes = self.nodes.lock().unwrap();
+            active_node(&mut nodes)
+        };
+        BitcoinClientImpl::from_node(self.http_client.clone(), &node)
+    }
+}
+
+/// Delivers payload to subscriber, retrying with exponential backoff
+pub fn deliver<C: WebhookClient + Clone, T: Serialize>(
+    client: C,
+    subscriber: WebhookSubscriber,
+    retry: WebhookRetry,
+    payload: &WebhookPayload<T>,
+) -> impl Future<Item = (), Error = ()> + Send {
+    let body = serde_json::to_string(payload).unwrap_or_default();
+    future::loop_fn(0, move |attempt| {
+        let retry = retry.clone();
+        let url = subscriber.url.clone();
+        client
+            .send(subscriber.url.clone(), subscriber.secret.clone(), body.clone())
+            .then(move |r| match r {
+                Ok(_) => Either::A(future::ok(Loop::Break(()))),
+                Err(ref e) if attempt + 1 >= retry.max_attempts => {
+                    log_warn(e);
+                    warn!("Giving up on webhook to {} after {} attempts", url, attempt + 1);
+                    Either::A(future::ok(Loop::Break(())))
+                }
+                Err(e) => {
+                    log_warn(&e);
+                    let backoff = 2u64
+                        .checked_pow(attempt)
+                        .and_then(|multiplier| retry.backoff.checked_mul(multiplier))
+                        .map(|backoff| backoff.min(retry.max_backoff))
+                        .unwrap_or(retry.max_backoff);
+                    let backoff = Duration::from_secs(backoff);
+                    Either::B(
+                        Delay::new(Instant::now() + backoff)
+                            .map_err(|e| error!("Error creating delay {}", e))
+                            .map(move |_| Loop::Continue(attempt + 1)),
+                    )
+                }
+            })
+    })
+}
+
+/// Spawns a thread that polls for chain changes and posts them to subscribers
+pub fn start<C: HttpClient + Clone>(config: WebhooksConfig, nodes: Arc<Mutex<BTreeMap<usize, BitcoinNode>>>, client: C) {
+    let interval = Duration::from_secs(config.interval);
+    thread::spawn(move || {
+        let mut core = tokio_core::reactor::Core::new().unwrap();
+        let handle = core.handle();
+        let watcher = ChainWatcher::new(Arc::new(client.clone()), nodes);
+        let webhook_client = WebhookClientImpl::new(Arc::new(client));
+        // each subscriber has its own queue delivered one payload at a time, so it gets events in order
+        // without slow subscribers blocking the next poll or the others
+        let queues: Vec<_> = config
+            .subscribers
+            .iter()
+            .map(|subscriber| {
+                let (sender, receiver) = mpsc::unbounded::<WebhookPayload<ChainEvent>>();
+                let webhook_client = webhook_client.clone();
+                let subscriber = subscriber.clone();
+                let retry = config.retry.clone();
+                handle
+                    .spawn(receiver.for_each(move |payload| deliver(webhook_client.clone(), subscriber.clone(), retry.clone(), &payload)));
+                sender
+            })
+            .collect();
+        core.run(
+            Interval::new(Instant::now(), interval)
+                .map_err(|e| {
+                    error!("Error creating interval {}", e);
+                })
+                .for_each(move |_| {
+                    let queues = queues.clone();
+                    watcher.poll().then(move |r| {
+                        for event in r.unwrap_or_default() {
+                            let payload = WebhookPayload::new(event);
+                            for queue in &queues {
+                                let _ = queue.unbounded_send(payload.clone());
+                            }
+                        }
+                        Ok(())
+                    })
+                }),
+        )
+    });
+}